@@ -0,0 +1,142 @@
+//! Matches queries against 1000+ archetype signatures, comparing `ComponentMask`
+//! with the sorted component vectors archetypes used before.
+//!
+//! The last case takes its IDs from instantiations of generic components, which have
+//! to stay inside the inline mask bits just like plain components.
+//!
+//! Run with `cargo bench -p boyko-ecs --bench archetype_matching`.
+
+use std::hint::black_box;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+use boyko_ecs::ecs::constants::COMPONENT_MASK_INLINE_BITS;
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::core::component_mask::ComponentMask;
+use boyko_ecs::ecs::core::query::QueryAccess;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const ARCHETYPES: usize = 1024;
+const QUERIES: usize = 64;
+const ROUNDS: usize = 200;
+
+/// Generic component, every `Pair<A, B>` instantiation is its own component type
+struct Pair<A, B>(PhantomData<(A, B)>);
+
+impl<A: 'static, B: 'static> Component for Pair<A, B> {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+macro_rules! pair_ids {
+    ($($a:ty),*; $b:tt) => {
+        vec![$(pair_ids!(@row $a; $b)),*].concat()
+    };
+    (@row $a:ty; ($($b:ty),*)) => {
+        vec![$(Pair::<$a, $b>::component_id()),*]
+    };
+}
+
+/// IDs of 144 instantiations of `Pair`
+fn generic_component_ids() -> Vec<ComponentId> {
+    pair_ids!(
+        u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, bool, char;
+        (u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, bool, char)
+    )
+}
+
+/// Random sorted component set with up to `max_components` IDs taken from `ids`
+fn random_components(rng: &mut StdRng, max_components: usize, ids: &[ComponentId]) -> Vec<ComponentId> {
+    let count = rng.random_range(1..=max_components);
+    let mut components: Vec<ComponentId> = (0..count)
+        .map(|_| ids[rng.random_range(0..ids.len())])
+        .collect();
+    components.sort_unstable();
+    components.dedup();
+    components
+}
+
+fn sorted_contains_all(components: &[ComponentId], required: &[ComponentId]) -> bool {
+    required.iter().all(|id| components.binary_search(id).is_ok())
+}
+
+fn sorted_intersects(components: &[ComponentId], excluded: &[ComponentId]) -> bool {
+    excluded.iter().any(|id| components.binary_search(id).is_ok())
+}
+
+fn measure(name: &str, mut run: impl FnMut() -> usize) {
+    // Warm up caches and branch predictors
+    black_box(run());
+
+    let start = Instant::now();
+    let mut matched = 0;
+    for _ in 0..ROUNDS {
+        matched += black_box(run());
+    }
+    let elapsed = start.elapsed();
+
+    let checks = (ROUNDS * ARCHETYPES * QUERIES) as u32;
+    println!(
+        "{name:<32} {:>10.2?} total {:>8.2?} per check ({} matches)",
+        elapsed,
+        Duration::from_nanos(1).max(elapsed / checks),
+        matched / ROUNDS,
+    );
+}
+
+fn bench(title: &str, ids: &[ComponentId]) {
+    let mut rng = StdRng::seed_from_u64(0xB0_1C0);
+
+    let archetypes: Vec<Vec<ComponentId>> = (0..ARCHETYPES)
+        .map(|_| random_components(&mut rng, 24, ids))
+        .collect();
+    let queries: Vec<(Vec<ComponentId>, Vec<ComponentId>)> = (0..QUERIES)
+        .map(|_| (random_components(&mut rng, 3, ids), random_components(&mut rng, 2, ids)))
+        .collect();
+
+    let archetype_masks: Vec<ComponentMask> = archetypes.iter()
+        .map(|components| components.iter().copied().collect())
+        .collect();
+    let query_accesses: Vec<QueryAccess> = queries.iter()
+        .map(|(with, without)| {
+            let mut access = QueryAccess::default();
+            with.iter().for_each(|&id| access.add_with(id));
+            without.iter().for_each(|&id| access.add_without(id));
+            access
+        })
+        .collect();
+
+    let id_range = ids.iter().max().map_or(0, |id| id + 1);
+    println!("{title}: {ARCHETYPES} archetypes x {QUERIES} queries, component IDs < {id_range}");
+
+    measure("sorted vectors", || {
+        queries.iter().map(|(with, without)| {
+            archetypes.iter()
+                .filter(|components| sorted_contains_all(components, with) && !sorted_intersects(components, without))
+                .count()
+        }).sum()
+    });
+
+    measure("component masks", || {
+        query_accesses.iter().map(|access| {
+            archetype_masks.iter()
+                .filter(|mask| access.matches_mask(mask))
+                .count()
+        }).sum()
+    });
+
+    println!();
+}
+
+fn main() {
+    bench("inline masks", &(0..128).collect::<Vec<_>>());
+    bench("heap masks", &(0..2048).collect::<Vec<_>>());
+
+    let generic_ids = generic_component_ids();
+    assert!(
+        generic_ids.iter().all(|&id| id < COMPONENT_MASK_INLINE_BITS),
+        "generic component IDs spill component masks to the heap",
+    );
+    bench("generic components", &generic_ids);
+}
@@ -11,6 +11,11 @@ pub const CACHE_LINE_SIZE: usize = 64;
 /// requires less alignment
 pub const MIN_ALIGNMENT: usize = 8;
 
+/// Granularity of arena blocks (8 bytes)
+/// Every block offset and size in an arena region is a multiple of it, so
+/// allocations aligned to at most this never need an alignment search
+pub const MIN_BLOCK_ALIGN: usize = 8;
+
 //
 // Chunk configuration
 //
@@ -50,6 +55,10 @@ pub const TINY_COMPONENT_THRESHOLD: usize = 16;
 pub const SMALL_COMPONENT_THRESHOLD: usize = 64;
 pub const MEDIUM_COMPONENT_THRESHOLD: usize = 256;
 
+/// Number of component IDs a component mask holds without a heap allocation
+/// Must be a multiple of 64
+pub const COMPONENT_MASK_INLINE_BITS: usize = 256;
+
 //
 // Archetype and entity configuration
 //
@@ -83,4 +92,68 @@ pub const MIN_COMPONENTS_FOR_COMPACTION: usize = 16;
 pub const INITIAL_FREE_SLOTS_CAPACITY: usize = 1024;
 
 /// Maximum percentage of empty chunks before pool reorganization is triggered
-pub const MAX_EMPTY_CHUNKS_RATIO: f32 = 0.2; // 20% empty chunks
\ No newline at end of file
+pub const MAX_EMPTY_CHUNKS_RATIO: f32 = 0.2; // 20% empty chunks
+
+//
+// Arena thread caches
+//
+
+/// Number of per-thread allocation caches an arena keeps
+/// Threads are spread over the caches by a thread-local slot number
+pub const THREAD_CACHE_STRIPES: usize = 16;
+
+/// Smallest size class served by the thread caches (in bytes)
+pub const THREAD_CACHE_MIN_BLOCK_SIZE: usize = 16;
+
+/// Largest size class served by the thread caches (in bytes)
+/// Larger allocations always go to the shared free blocks
+pub const THREAD_CACHE_MAX_BLOCK_SIZE: usize = 4096;
+
+/// Number of blocks taken from the shared free blocks at once when a cache runs empty
+pub const THREAD_CACHE_REFILL_BLOCKS: usize = 16;
+
+/// Maximum number of cached blocks per size class, the rest is returned to the shared free blocks
+pub const THREAD_CACHE_MAX_BLOCKS: usize = 64;
+
+/// Default size of the arena scratch region in bytes (1MB)
+/// The region is taken from the arena on the first scratch allocation
+pub const DEFAULT_SCRATCH_SIZE: usize = 1024 * 1024;
+
+//
+// Arena mmap backend
+//
+
+/// Size of a huge page on x86_64 and aarch64 (2MB)
+/// Commits and releases of huge page regions happen in these steps
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Smallest freed block whose pages are returned to the OS with `MADV_DONTNEED` (64KB)
+/// Smaller blocks keep their pages, they are likely to be reused soon
+pub const MMAP_RELEASE_THRESHOLD: usize = 64 * 1024;
+
+//
+// TLSF free blocks
+//
+
+/// Log2 of the number of second-level lists per power of two in the TLSF free blocks
+/// Higher values waste less space per block at the cost of larger bitmaps
+pub const TLSF_SECOND_LEVEL_LOG2: usize = 4;
+
+//
+// Debug arena
+//
+
+/// Canary bytes placed before and after every allocation with the `debug-arena` feature
+pub const DEBUG_CANARY_SIZE: usize = 16;
+
+/// Fill pattern of the canaries
+pub const DEBUG_CANARY_BYTE: u8 = 0xFD;
+
+/// Fill pattern of freshly allocated memory, reading it means the memory was never written
+pub const DEBUG_ALLOC_BYTE: u8 = 0xCD;
+
+/// Fill pattern of freed memory, reading it means a use after free
+pub const DEBUG_POISON_BYTE: u8 = 0xDD;
+
+/// Smallest allocation placed right before a guard page when guard pages are enabled (16KB)
+pub const DEBUG_GUARD_PAGE_THRESHOLD: usize = 16 * 1024;
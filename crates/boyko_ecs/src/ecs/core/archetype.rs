@@ -0,0 +1,299 @@
+use std::ptr::NonNull;
+use std::sync::Arc;
+use crate::ecs::constants::INITIAL_ENTITY_CAPACITY;
+use crate::ecs::core::component::{Component, ComponentId, ComponentInfo};
+use crate::ecs::core::component_mask::ComponentMask;
+use crate::ecs::core::entity::Entity;
+use crate::ecs::error::EcsResult;
+use crate::ecs::memory::allocation_tracker::PoolTag;
+use crate::ecs::memory::arena::Relocations;
+use crate::ecs::memory::chunk_allocator::ChunkAllocator;
+use crate::ecs::memory::component_index::UnitId;
+use crate::ecs::memory::component_pool::{ComponentPool, ComponentStorage};
+
+pub type ArchetypeId = usize;
+
+/// Table of all entities sharing the same set of table components
+///
+/// Every component type gets its own `ComponentPool` column, and one more pool holds
+/// the entities themselves. All columns share the same chunk capacity and always
+/// receive the same operations, so a row has the same `UnitId` in every column.
+///
+/// Zero-sized components are pure markers: they are part of the component set
+/// but have no column and take no memory.
+pub struct Archetype {
+    id: ArchetypeId,
+
+    /// Sorted IDs of the components stored in this archetype, markers included
+    components: Vec<ComponentId>,
+
+    /// The same component set as a bitset, used for matching
+    mask: ComponentMask,
+
+    /// Sorted IDs of the components that have a column
+    column_ids: Vec<ComponentId>,
+
+    /// Component columns, parallel to `column_ids`
+    columns: Vec<Box<dyn ComponentStorage>>,
+
+    /// Drop glue of the marker components that need it, run when a row is destroyed
+    marker_drops: Vec<fn()>,
+
+    /// Entity column, the owner of each row
+    entities: ComponentPool<Entity>,
+}
+
+impl Archetype {
+    /// Creates an archetype for the given components, `infos` must be sorted by ID
+    #[track_caller]
+    pub fn new(id: ArchetypeId, allocator: &Arc<dyn ChunkAllocator>, infos: &[ComponentInfo]) -> Self {
+        Self::try_new(id, allocator, infos)
+            .unwrap_or_else(|error| panic!("Failed to allocate archetype: {error}"))
+    }
+
+    /// Creates an archetype, failing if the allocator cannot hold its columns
+    #[track_caller]
+    pub fn try_new(id: ArchetypeId, allocator: &Arc<dyn ChunkAllocator>, infos: &[ComponentInfo]) -> EcsResult<Self> {
+        Self::try_with_allocators(id, allocator, infos, |_| Arc::clone(allocator))
+    }
+
+    /// Creates an archetype whose columns take their chunks from `column_allocator`,
+    /// the entity column takes them from `allocator`
+    #[track_caller]
+    pub fn try_with_allocators(
+        id: ArchetypeId,
+        allocator: &Arc<dyn ChunkAllocator>,
+        infos: &[ComponentInfo],
+        column_allocator: impl Fn(&ComponentInfo) -> Arc<dyn ChunkAllocator>,
+    ) -> EcsResult<Self> {
+        debug_assert!(infos.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+        let (markers, stored): (Vec<&ComponentInfo>, Vec<&ComponentInfo>) = infos.iter()
+            .partition(|info| info.is_marker());
+
+        // Columns must stay in lockstep, so they all use the smallest chunk capacity
+        let components_per_chunk = stored.iter()
+            .map(|info| info.chunk_capacity)
+            .min()
+            .unwrap_or_else(ComponentPool::<Entity>::get_optimal_chunk_capacity);
+
+        let columns = stored.iter()
+            .map(|info| {
+                let tag = PoolTag::Column { archetype: id, component: info.id };
+                info.create_table_storage(&column_allocator(info), components_per_chunk, tag)
+            })
+            .collect();
+        let entities = ComponentPool::try_new_tagged(allocator, 0, components_per_chunk, PoolTag::Entities { archetype: id })?;
+
+        let mut archetype = Self {
+            id,
+            components: infos.iter().map(|info| info.id).collect(),
+            mask: infos.iter().map(|info| info.id).collect(),
+            column_ids: stored.iter().map(|info| info.id).collect(),
+            columns,
+            marker_drops: markers.iter().filter_map(|info| info.marker_drop()).collect(),
+            entities,
+        };
+
+        // Allocated here rather than by the pools, so tracked chunks point at our caller
+        archetype.try_reserve(INITIAL_ENTITY_CAPACITY)?;
+        Ok(archetype)
+    }
+
+    #[inline]
+    pub fn id(&self) -> ArchetypeId {
+        self.id
+    }
+
+    /// Sorted IDs of the components stored in this archetype
+    #[inline]
+    pub fn components(&self) -> &[ComponentId] {
+        &self.components
+    }
+
+    /// Component set of this archetype as a bitset
+    #[inline]
+    pub fn mask(&self) -> &ComponentMask {
+        &self.mask
+    }
+
+    #[inline]
+    pub fn contains(&self, component_id: ComponentId) -> bool {
+        self.mask.contains(component_id)
+    }
+
+    /// Number of entities in the archetype
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entities.count()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of rows the archetype can hold before it has to grow
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.entities.capacity()
+    }
+
+    /// Checks if the next row makes the archetype grow
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.entities.is_full()
+    }
+
+    /// Grows the entity column and every component column until `additional` rows fit
+    ///
+    /// Columns grow independently, but new chunks are only appended, so the rows
+    /// keep the same `UnitId` in every column even if one of them fails to grow.
+    #[track_caller]
+    pub fn try_reserve(&mut self, additional: usize) -> EcsResult<()> {
+        self.entities.try_reserve(additional)?;
+
+        for column in &mut self.columns {
+            column.try_reserve(additional)?;
+        }
+
+        Ok(())
+    }
+
+    /// Entity column of the archetype
+    #[inline]
+    pub fn entities(&self) -> &ComponentPool<Entity> {
+        &self.entities
+    }
+
+    pub fn column(&self, component_id: ComponentId) -> Option<&dyn ComponentStorage> {
+        let index = self.column_ids.binary_search(&component_id).ok()?;
+        Some(self.columns[index].as_ref())
+    }
+
+    pub fn column_mut(&mut self, component_id: ComponentId) -> Option<&mut dyn ComponentStorage> {
+        let index = self.column_ids.binary_search(&component_id).ok()?;
+        Some(self.columns[index].as_mut())
+    }
+
+    /// Typed access to a component column
+    pub fn pool<T: Component>(&self) -> Option<&ComponentPool<T>> {
+        self.column(T::component_id())?.as_any().downcast_ref()
+    }
+
+    /// Typed mutable access to a component column
+    pub fn pool_mut<T: Component>(&mut self) -> Option<&mut ComponentPool<T>> {
+        self.column_mut(T::component_id())?.as_any_mut().downcast_mut()
+    }
+
+    /// Pointer to a marker component of this archetype
+    ///
+    /// Markers have no storage, every row shares the same dangling pointer.
+    #[inline]
+    pub fn marker_ptr<T: Component>(&self) -> Option<NonNull<T>> {
+        (T::size() == 0 && self.contains(T::component_id())).then(NonNull::dangling)
+    }
+
+    /// Adds a row for the entity, component columns must be filled by the caller
+    ///
+    /// Returns `None` if a column cannot grow.
+    #[track_caller]
+    pub fn push_entity(&mut self, entity: Entity) -> Option<UnitId> {
+        self.try_reserve(1).ok()?;
+        self.entities.add(entity)
+    }
+
+    /// Removes a row, dropping all of its components
+    ///
+    /// Returns the entity that was moved into the freed row, if any.
+    pub fn swap_remove(&mut self, row: UnitId) -> Option<Entity> {
+        for column in &mut self.columns {
+            column.swap_remove(row);
+        }
+
+        for marker_drop in &self.marker_drops {
+            marker_drop();
+        }
+
+        self.finish_swap_remove(row)
+    }
+
+    /// Moves a row into `target`, which must contain every component of this archetype
+    /// except the ones passed to `on_missing`
+    ///
+    /// Columns that `target` does not store are handed to `on_missing`, which has to
+    /// remove them from the column at `row`. Markers are not moved at all, a marker
+    /// missing in `target` is owned by the caller from now on.
+    ///
+    /// Returns the new row in `target` and the entity that was moved into the freed
+    /// row of this archetype, if any.
+    #[track_caller]
+    pub fn move_entity(
+        &mut self,
+        row: UnitId,
+        target: &mut Archetype,
+        mut on_missing: impl FnMut(&mut dyn ComponentStorage, UnitId),
+    ) -> Option<(UnitId, Option<Entity>)> {
+        let entity = *self.entities.get(row)?;
+        let new_row = target.push_entity(entity)?;
+
+        for (component_id, column) in self.column_ids.iter().zip(self.columns.iter_mut()) {
+            match target.column_mut(*component_id) {
+                Some(target_column) => {
+                    let moved_row = column.move_to(row, target_column);
+                    debug_assert_eq!(moved_row, Some(new_row), "Archetype columns out of lockstep");
+                }
+                None => on_missing(column.as_mut(), row),
+            }
+        }
+
+        Some((new_row, self.finish_swap_remove(row)))
+    }
+
+    /// Checks if the rows are spread over too many chunks, see `ComponentPool::needs_compaction`
+    #[inline]
+    pub fn needs_compaction(&self) -> bool {
+        self.entities.needs_compaction()
+    }
+
+    /// Moves rows out of sparse chunks into fuller ones
+    ///
+    /// Returns the moved rows as `(old, new)` pairs, the entity index has to be
+    /// pointed to the new rows.
+    pub fn compact(&mut self) -> Vec<(UnitId, UnitId)> {
+        let remap = self.entities.compact();
+
+        for column in &mut self.columns {
+            let column_remap = column.compact();
+            debug_assert_eq!(column_remap, remap, "Archetype columns out of lockstep");
+        }
+
+        remap
+    }
+
+    /// Points the entity pool and all columns to the chunks moved by an arena compaction
+    pub fn relocate(&mut self, relocations: &Relocations) {
+        self.entities.relocate(relocations);
+
+        for column in &mut self.columns {
+            column.relocate(relocations);
+        }
+    }
+
+    /// Removes the entity of an already emptied row and reports who took its place
+    fn finish_swap_remove(&mut self, row: UnitId) -> Option<Entity> {
+        self.entities.swap_remove(row);
+        self.entities.get(row).copied()
+    }
+}
+
+impl Drop for Archetype {
+    fn drop(&mut self) {
+        // Columns drop their components themselves, markers only exist logically
+        for _ in 0..self.len() {
+            for marker_drop in &self.marker_drops {
+                marker_drop();
+            }
+        }
+    }
+}
@@ -1,9 +1,29 @@
 use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use crate::ecs::memory::allocation_tracker::PoolTag;
+use crate::ecs::memory::chunk_allocator::ChunkAllocator;
+use crate::ecs::memory::component_pool::{ComponentPool, ComponentStorage};
+use crate::ecs::memory::sparse_set::{SparseSet, SparseStorage};
 
 pub type ComponentId = usize;
 
+/// How the components of a type are stored inside the ECS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StorageType {
+    /// Components live in archetype tables, packed into chunks
+    #[default]
+    Table,
+
+    /// Components live in a sparse set keyed by entity
+    Sparse,
+}
+
 pub trait Component: 'static + Sized {
-    #[inline(always)]
     fn component_id() -> ComponentId;
 
     #[inline(always)]
@@ -11,6 +31,24 @@ pub trait Component: 'static + Sized {
         std::any::type_name::<Self>()
     }
 
+    /// Human readable component name, `#[component(name = "...")]` overrides it
+    #[inline(always)]
+    fn name() -> &'static str {
+        Self::debug_type_name()
+    }
+
+    /// Storage backend for this component, `#[component(storage = "...")]` overrides it
+    #[inline(always)]
+    fn storage_type() -> StorageType {
+        StorageType::Table
+    }
+
+    /// Number of components per chunk, `None` picks it from the component size
+    #[inline(always)]
+    fn chunk_capacity() -> Option<usize> {
+        None
+    }
+
     #[inline(always)]
     fn type_id() -> TypeId {
         TypeId::of::<Self>()
@@ -26,3 +64,180 @@ pub trait Component: 'static + Sized {
         std::mem::align_of::<Self>()
     }
 }
+
+/// Creates an empty component pool from its allocator, chunk capacity and tag
+///
+/// Fn pointers drop `#[track_caller]`, so the pool allocates nothing here and the
+/// caller reserves its chunks through `ComponentStorage::try_reserve`.
+type TableStorageFactory = fn(&Arc<dyn ChunkAllocator>, usize, PoolTag) -> Box<dyn ComponentStorage>;
+
+/// Creates a sparse set that takes its chunks from the allocator
+type SparseStorageFactory = fn(&Arc<dyn ChunkAllocator>) -> Box<dyn SparseStorage>;
+
+/// Type-erased description of a component type
+///
+/// Lets archetypes and the ECS master create storages for component types
+/// they only know by ID.
+#[derive(Clone, Copy)]
+pub struct ComponentInfo {
+    pub id: ComponentId,
+    pub name: &'static str,
+    pub type_id: TypeId,
+    pub size: usize,
+    pub alignment: usize,
+    pub storage_type: StorageType,
+
+    /// Number of components per chunk, resolved from the attributes and the size
+    pub chunk_capacity: usize,
+
+    new_table_storage: TableStorageFactory,
+    new_sparse_storage: SparseStorageFactory,
+
+    /// Drops a zero-sized component, set only if the type needs drop
+    marker_drop: Option<fn()>,
+}
+
+impl ComponentInfo {
+    pub fn of<T: Component>() -> Self {
+        Self {
+            id: T::component_id(),
+            name: T::name(),
+            type_id: T::type_id(),
+            size: T::size(),
+            alignment: T::alignment(),
+            storage_type: T::storage_type(),
+            chunk_capacity: ComponentPool::<T>::get_optimal_chunk_capacity(),
+            new_table_storage: |allocator, components_per_chunk, tag| {
+                // Empty pools allocate nothing, so creating one cannot fail
+                let pool = ComponentPool::<T>::try_new_tagged(allocator, 0, components_per_chunk, tag);
+                Box::new(pool.expect("Empty pool allocates no chunks"))
+            },
+            new_sparse_storage: |allocator| Box::new(SparseSet::<T>::with_default_sizes(allocator)),
+            marker_drop: (T::size() == 0 && std::mem::needs_drop::<T>()).then_some(|| unsafe {
+                std::ptr::drop_in_place(NonNull::<T>::dangling().as_ptr())
+            }),
+        }
+    }
+
+    /// Zero-sized table components are pure archetype markers without a column
+    #[inline]
+    pub fn is_marker(&self) -> bool {
+        self.size == 0 && self.storage_type == StorageType::Table
+    }
+
+    /// Drop glue of a marker component, `None` if dropping it does nothing
+    #[inline]
+    pub fn marker_drop(&self) -> Option<fn()> {
+        self.marker_drop
+    }
+
+    /// Creates an empty component pool for an archetype table, without any chunks
+    pub fn create_table_storage(&self, allocator: &Arc<dyn ChunkAllocator>, components_per_chunk: usize, tag: PoolTag) -> Box<dyn ComponentStorage> {
+        (self.new_table_storage)(allocator, components_per_chunk, tag)
+    }
+
+    /// Creates an empty sparse set
+    pub fn create_sparse_storage(&self, allocator: &Arc<dyn ChunkAllocator>) -> Box<dyn SparseStorage> {
+        (self.new_sparse_storage)(allocator)
+    }
+}
+
+/// Hasher for `TypeId` keys, which already are well distributed hashes
+#[derive(Default)]
+struct TypeIdHasher(u64);
+
+impl Hasher for TypeIdHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) {
+        self.0 ^= value;
+    }
+}
+
+type ComponentIdCache = HashMap<TypeId, ComponentId, BuildHasherDefault<TypeIdHasher>>;
+
+/// Returns the component ID of a type, registering it on first use
+///
+/// All component types share one dense counter starting at 0, so IDs stay inside the
+/// inline bits of `ComponentMask`. Every instantiation of a generic component
+/// (`Foo<u32>`, `Foo<f32>`, ...) gets its own ID.
+///
+/// Generic types cannot have a static per instantiation, so IDs are cached per thread.
+/// The global registry is only locked the first time a thread asks for a type.
+/// Non-generic types should go through a `ComponentIdCell` instead.
+#[inline]
+pub fn dynamic_component_id<T: 'static>() -> ComponentId {
+    thread_local! {
+        static CACHE: RefCell<ComponentIdCache> = RefCell::default();
+    }
+
+    let type_id = TypeId::of::<T>();
+    let cached = CACHE.try_with(|cache| cache.borrow().get(&type_id).copied());
+    if let Ok(Some(id)) = cached {
+        return id;
+    }
+
+    let id = register_component_id(type_id);
+
+    // The cache is gone while the thread shuts down, the registry still answers then
+    let _ = CACHE.try_with(|cache| cache.borrow_mut().insert(type_id, id));
+    id
+}
+
+#[cold]
+fn register_component_id(type_id: TypeId) -> ComponentId {
+    static REGISTRY: OnceLock<Mutex<HashMap<TypeId, ComponentId>>> = OnceLock::new();
+
+    let registry = REGISTRY.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut ids = registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let next_id = ids.len();
+    *ids.entry(type_id).or_insert(next_id)
+}
+
+/// Caches the component ID of one non-generic type in a `static`
+///
+/// After the first call the ID is a single atomic load.
+pub struct ComponentIdCell {
+    id: AtomicUsize,
+}
+
+impl ComponentIdCell {
+    const UNASSIGNED: ComponentId = ComponentId::MAX;
+
+    pub const fn new() -> Self {
+        Self { id: AtomicUsize::new(Self::UNASSIGNED) }
+    }
+
+    #[inline]
+    pub fn get_or_register<T: 'static>(&self) -> ComponentId {
+        match self.id.load(Ordering::Relaxed) {
+            Self::UNASSIGNED => self.register::<T>(),
+            id => id,
+        }
+    }
+
+    #[cold]
+    fn register<T: 'static>(&self) -> ComponentId {
+        let id = dynamic_component_id::<T>();
+        self.id.store(id, Ordering::Relaxed);
+        id
+    }
+}
+
+impl Default for ComponentIdCell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use crate::ecs::constants::COMPONENT_MASK_INLINE_BITS;
+use crate::ecs::core::component::ComponentId;
+
+const WORD_BITS: usize = u64::BITS as usize;
+const INLINE_WORDS: usize = COMPONENT_MASK_INLINE_BITS / WORD_BITS;
+
+#[derive(Clone)]
+enum MaskWords {
+    /// Fixed-width storage covering component IDs below `COMPONENT_MASK_INLINE_BITS`
+    Inline([u64; INLINE_WORDS]),
+
+    /// Heap storage, used once an ID does not fit into the inline words
+    Heap(Vec<u64>),
+}
+
+/// Bitset of component IDs
+///
+/// Archetypes and queries compare component sets with it: a subset check is one
+/// AND and compare per 64 components. IDs up to `COMPONENT_MASK_INLINE_BITS` are
+/// stored inline, larger IDs (e.g. generic component instantiations) spill the
+/// mask to the heap.
+#[derive(Clone)]
+pub struct ComponentMask {
+    words: MaskWords,
+}
+
+impl ComponentMask {
+    pub const fn new() -> Self {
+        Self { words: MaskWords::Inline([0; INLINE_WORDS]) }
+    }
+
+    #[inline(always)]
+    fn words(&self) -> &[u64] {
+        match &self.words {
+            MaskWords::Inline(words) => words,
+            MaskWords::Heap(words) => words,
+        }
+    }
+
+    #[inline(always)]
+    fn word(&self, index: usize) -> u64 {
+        self.words().get(index).copied().unwrap_or(0)
+    }
+
+    /// Mutable access to the words, spilling to the heap so `word_index` fits
+    fn words_mut(&mut self, word_index: usize) -> &mut [u64] {
+        if let MaskWords::Inline(words) = &self.words {
+            if word_index < INLINE_WORDS {
+                let MaskWords::Inline(words) = &mut self.words else { unreachable!() };
+                return words;
+            }
+
+            self.words = MaskWords::Heap(words.to_vec());
+        }
+
+        let MaskWords::Heap(words) = &mut self.words else { unreachable!() };
+        if word_index >= words.len() {
+            words.resize(word_index + 1, 0);
+        }
+        words
+    }
+
+    #[inline]
+    pub fn insert(&mut self, component_id: ComponentId) {
+        let (word_index, bit) = (component_id / WORD_BITS, component_id % WORD_BITS);
+        self.words_mut(word_index)[word_index] |= 1 << bit;
+    }
+
+    #[inline]
+    pub fn remove(&mut self, component_id: ComponentId) {
+        let (word_index, bit) = (component_id / WORD_BITS, component_id % WORD_BITS);
+        if word_index < self.words().len() {
+            self.words_mut(word_index)[word_index] &= !(1 << bit);
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, component_id: ComponentId) -> bool {
+        self.word(component_id / WORD_BITS) & (1 << (component_id % WORD_BITS)) != 0
+    }
+
+    /// Checks if every component of `other` is also in this mask
+    #[inline]
+    pub fn contains_all(&self, other: &ComponentMask) -> bool {
+        match (&self.words, &other.words) {
+            (MaskWords::Inline(words), MaskWords::Inline(other_words)) => {
+                words.iter().zip(other_words)
+                    .fold(0, |missing, (word, other_word)| missing | (other_word & !word)) == 0
+            }
+            _ => {
+                let words = self.words();
+                other.significant_words().iter().enumerate()
+                    .all(|(index, &other_word)| {
+                        other_word == 0 || other_word & !words.get(index).copied().unwrap_or(0) == 0
+                    })
+            }
+        }
+    }
+
+    /// Checks if the masks have at least one component in common
+    #[inline]
+    pub fn intersects(&self, other: &ComponentMask) -> bool {
+        match (&self.words, &other.words) {
+            (MaskWords::Inline(words), MaskWords::Inline(other_words)) => {
+                words.iter().zip(other_words)
+                    .fold(0, |common, (word, other_word)| common | (word & other_word)) != 0
+            }
+            _ => self.significant_words().iter().zip(other.significant_words())
+                .any(|(word, other_word)| word & other_word != 0),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.words().iter().all(|&word| word == 0)
+    }
+
+    /// Number of components in the mask
+    pub fn len(&self) -> usize {
+        self.words().iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Iterates over the component IDs in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.words().iter().enumerate().flat_map(|(index, &word)| {
+            let mut remaining = word;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+
+                let bit = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                Some(index * WORD_BITS + bit)
+            })
+        })
+    }
+
+    /// Words without trailing zeros, equal masks have equal significant words
+    /// regardless of whether they spilled to the heap
+    fn significant_words(&self) -> &[u64] {
+        let words = self.words();
+        let len = words.iter().rposition(|&word| word != 0).map_or(0, |index| index + 1);
+        &words[..len]
+    }
+}
+
+impl Default for ComponentMask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for ComponentMask {
+    fn eq(&self, other: &Self) -> bool {
+        self.significant_words() == other.significant_words()
+    }
+}
+
+impl Eq for ComponentMask {}
+
+impl Hash for ComponentMask {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.significant_words().hash(state);
+    }
+}
+
+impl fmt::Debug for ComponentMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<ComponentId> for ComponentMask {
+    fn from_iter<I: IntoIterator<Item = ComponentId>>(iter: I) -> Self {
+        let mut mask = Self::new();
+        mask.extend(iter);
+        mask
+    }
+}
+
+impl Extend<ComponentId> for ComponentMask {
+    fn extend<I: IntoIterator<Item = ComponentId>>(&mut self, iter: I) {
+        for component_id in iter {
+            self.insert(component_id);
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use crate::ecs::core::archetype::{Archetype, ArchetypeId};
+use crate::ecs::core::component::{Component, ComponentId, ComponentInfo, StorageType};
+use crate::ecs::core::component_mask::ComponentMask;
+use crate::ecs::core::entity::{Entities, Entity, EntityLocation};
+use crate::ecs::core::query::{QueryData, QueryFilter, QueryIter};
+use crate::ecs::error::{EcsError, EcsResult};
+use crate::ecs::memory::arena::Arena;
+use crate::ecs::memory::chunk_allocator::ChunkAllocator;
+use crate::ecs::memory::component_pool::ComponentPool;
+use crate::ecs::memory::sparse_set::{SparseSet, SparseStorage};
+
+/// Owner of all entities, archetypes and component storages
+///
+/// Table components are grouped into archetypes by their component set. Components
+/// declared with `#[component(storage = "sparse")]` live in per-type sparse sets instead,
+/// so adding and removing them never moves the entity between archetypes.
+/// Zero-sized table components are stored as archetype markers without any memory.
+pub struct EcsMaster {
+    /// All archetypes, indexed by `ArchetypeId`; archetype 0 has no components
+    archetypes: Vec<Archetype>,
+
+    /// Archetype lookup by its component set
+    archetype_index: HashMap<ComponentMask, ArchetypeId>,
+
+    /// Sparse sets of the sparse components
+    sparse_sets: HashMap<ComponentId, Box<dyn SparseStorage>>,
+
+    /// Registered component types
+    components: HashMap<ComponentId, ComponentInfo>,
+
+    /// Entity index, maps every entity to its archetype and row
+    entities: Entities,
+
+    /// Chunk allocators of the component types that do not use the arena
+    chunk_allocators: HashMap<ComponentId, Arc<dyn ChunkAllocator>>,
+
+    /// Backing memory of all storages, shared with every chunk allocated from it
+    arena: Arc<Arena>,
+}
+
+impl EcsMaster {
+    #[track_caller]
+    pub fn new() -> Self {
+        Self::with_arena(Arena::new())
+    }
+
+    #[track_caller]
+    pub fn with_arena(arena: Arena) -> Self {
+        let arena = Arc::new(arena);
+        let empty = Archetype::new(0, &(arena.clone() as Arc<dyn ChunkAllocator>), &[]);
+
+        Self {
+            archetypes: vec![empty],
+            archetype_index: HashMap::from([(ComponentMask::new(), 0)]),
+            sparse_sets: HashMap::new(),
+            components: HashMap::new(),
+            entities: Entities::new(),
+            chunk_allocators: HashMap::new(),
+            arena,
+        }
+    }
+
+    /// Creates an entity without components
+    #[track_caller]
+    pub fn spawn(&mut self) -> Entity {
+        self.try_spawn()
+            .unwrap_or_else(|error| panic!("Failed to spawn an entity: {error}"))
+    }
+
+    /// Creates an entity without components, failing with `OutOfMemory` if the empty archetype cannot grow
+    ///
+    /// The entity ID is only taken once the row is reserved, a failed spawn leaves nothing behind.
+    #[track_caller]
+    pub fn try_spawn(&mut self) -> EcsResult<Entity> {
+        self.archetypes[0].try_reserve(1)?;
+
+        let entity = self.entities.alloc();
+        let row = self.archetypes[0].push_entity(entity)
+            .expect("Row of the empty archetype is reserved");
+
+        self.entities.set_location(entity.id, EntityLocation { archetype_id: 0, row });
+        Ok(entity)
+    }
+
+    /// Destroys the entity together with all of its components
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        let Some(location) = self.entities.free(entity) else {
+            return false;
+        };
+
+        let moved = self.archetypes[location.archetype_id].swap_remove(location.row);
+        self.relocate(moved, location);
+
+        for set in self.sparse_sets.values_mut() {
+            set.remove(entity.id);
+        }
+
+        true
+    }
+
+    #[inline]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.is_alive(entity)
+    }
+
+    /// Adds a component to the entity, replacing the existing one
+    ///
+    /// Returns false if the entity is dead or the target archetype cannot grow.
+    #[track_caller]
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> bool {
+        self.try_insert(entity, component).is_ok()
+    }
+
+    /// Adds a component to the entity, replacing the existing one
+    ///
+    /// Fails with `StaleEntity` if the entity is dead and `OutOfMemory` if the storage
+    /// of the target archetype cannot be allocated or grown.
+    /// The component is dropped on failure.
+    #[track_caller]
+    pub fn try_insert<T: Component>(&mut self, entity: Entity, component: T) -> EcsResult<()> {
+        let location = self.location(entity).ok_or(EcsError::StaleEntity(entity))?;
+
+        self.register_component::<T>();
+
+        if T::storage_type() == StorageType::Sparse {
+            self.sparse_set_mut::<T>().try_insert(entity, component)?;
+            return Ok(());
+        }
+
+        let source = &mut self.archetypes[location.archetype_id];
+        if source.marker_ptr::<T>().is_some() {
+            // Markers are indistinguishable, replacing one is the same as dropping the new one
+            drop(component);
+            return Ok(());
+        }
+
+        if let Some(pool) = source.pool_mut::<T>() {
+            let slot = pool.get_mut(location.row).ok_or(EcsError::MissingComponent(T::name()))?;
+            *slot = component;
+            return Ok(());
+        }
+
+        let mut components = source.mask().clone();
+        components.insert(T::component_id());
+
+        let target_id = self.try_get_or_create_archetype(components)?;
+        // Grow the target up front, so a failed allocation leaves the entity where it was
+        self.archetypes[target_id].try_reserve(1)?;
+
+        let (source, target) = Self::archetype_pair(&mut self.archetypes, location.archetype_id, target_id);
+        let (row, moved) = source.move_entity(location.row, target, |_, _| {
+            unreachable!("Target archetype has all source components")
+        }).expect("Target archetype has a reserved row");
+
+        if T::size() == 0 {
+            // Markers have no storage, the archetype now owns the value logically
+            std::mem::forget(component);
+        } else {
+            let added = target.pool_mut::<T>().and_then(|pool| pool.add(component));
+            debug_assert_eq!(added, Some(row), "Archetype columns out of lockstep");
+        }
+
+        self.relocate(moved, location);
+        self.entities.set_location(entity.id, EntityLocation { archetype_id: target_id, row });
+        Ok(())
+    }
+
+    /// Removes a component from the entity and returns it
+    #[track_caller]
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.try_remove(entity).ok()
+    }
+
+    /// Removes a component from the entity and returns it
+    ///
+    /// Fails with `StaleEntity` if the entity is dead and `MissingComponent`
+    /// if it does not have the component.
+    #[track_caller]
+    pub fn try_remove<T: Component>(&mut self, entity: Entity) -> EcsResult<T> {
+        let location = self.location(entity).ok_or(EcsError::StaleEntity(entity))?;
+        let missing = EcsError::MissingComponent(T::name());
+
+        if T::storage_type() == StorageType::Sparse {
+            return self.sparse_set_mut_existing::<T>()
+                .and_then(|set| set.remove(entity.id))
+                .ok_or(missing);
+        }
+
+        let source = &self.archetypes[location.archetype_id];
+        if !source.contains(T::component_id()) {
+            return Err(missing);
+        }
+
+        let mut components = source.mask().clone();
+        components.remove(T::component_id());
+
+        let target_id = self.try_get_or_create_archetype(components)?;
+        // Grow the target up front, so a failed allocation leaves the entity where it was
+        self.archetypes[target_id].try_reserve(1)?;
+
+        let mut removed = None;
+        let (source, target) = Self::archetype_pair(&mut self.archetypes, location.archetype_id, target_id);
+        let (row, moved) = source.move_entity(location.row, target, |column, row| {
+            let pool: &mut ComponentPool<T> = column.as_any_mut().downcast_mut()
+                .expect("Component storage of a different type");
+            removed = pool.swap_take(row);
+        }).expect("Target archetype has a reserved row");
+
+        if T::size() == 0 {
+            // The marker was forgotten on insert, hand the value back to the caller
+            removed = Some(unsafe { NonNull::<T>::dangling().as_ptr().read() });
+        }
+
+        self.relocate(moved, location);
+        self.entities.set_location(entity.id, EntityLocation { archetype_id: target_id, row });
+        removed.ok_or(missing)
+    }
+
+    /// Gets a reference to the entity's component
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.try_get(entity).ok()
+    }
+
+    /// Gets a reference to the entity's component, telling a dead entity from a missing component
+    pub fn try_get<T: Component>(&self, entity: Entity) -> EcsResult<&T> {
+        let location = self.location(entity).ok_or(EcsError::StaleEntity(entity))?;
+
+        let component = match T::storage_type() {
+            StorageType::Table => {
+                let archetype = &self.archetypes[location.archetype_id];
+                if let Some(marker) = archetype.marker_ptr::<T>() {
+                    return Ok(unsafe { marker.as_ref() });
+                }
+
+                archetype.pool::<T>().and_then(|pool| pool.get(location.row))
+            }
+            StorageType::Sparse => self.sparse_set::<T>().and_then(|set| set.get(entity.id)),
+        };
+
+        component.ok_or(EcsError::MissingComponent(T::name()))
+    }
+
+    /// Gets a mutable reference to the entity's component
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.try_get_mut(entity).ok()
+    }
+
+    /// Gets a mutable reference to the entity's component, telling a dead entity from a missing component
+    pub fn try_get_mut<T: Component>(&mut self, entity: Entity) -> EcsResult<&mut T> {
+        let location = self.location(entity).ok_or(EcsError::StaleEntity(entity))?;
+
+        let component = match T::storage_type() {
+            StorageType::Table => {
+                let archetype = &mut self.archetypes[location.archetype_id];
+                if let Some(mut marker) = archetype.marker_ptr::<T>() {
+                    return Ok(unsafe { marker.as_mut() });
+                }
+
+                archetype.pool_mut::<T>().and_then(|pool| pool.get_mut(location.row))
+            }
+            StorageType::Sparse => self.sparse_set_mut_existing::<T>().and_then(|set| set.get_mut(entity.id)),
+        };
+
+        component.ok_or(EcsError::MissingComponent(T::name()))
+    }
+
+    /// Checks if the entity has the component
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        let Some(location) = self.location(entity) else {
+            return false;
+        };
+
+        match T::storage_type() {
+            StorageType::Table => self.archetypes[location.archetype_id].contains(T::component_id()),
+            StorageType::Sparse => self.sparse_set::<T>().is_some_and(|set| set.contains(entity.id)),
+        }
+    }
+
+    /// Iterates over all entities matching the query
+    ///
+    /// Table and sparse components can be mixed freely, e.g. `(&Position, &mut Selected)`.
+    pub fn query<Q: QueryData>(&mut self) -> QueryIter<'_, Q, ()> {
+        QueryIter::new(self)
+    }
+
+    /// Iterates over all entities matching the query and the filter,
+    /// e.g. `query_filtered::<&Position, With<Player>>()`
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        QueryIter::new(self)
+    }
+
+    /// Number of alive entities
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    pub fn archetype(&self, id: ArchetypeId) -> Option<&Archetype> {
+        self.archetypes.get(id)
+    }
+
+    /// Gets the location of the entity's table components
+    #[inline]
+    pub fn location(&self, entity: Entity) -> Option<EntityLocation> {
+        self.entities.location(entity)
+    }
+
+    /// Sparse set of a sparse component, if any entity ever had it
+    pub fn sparse_set<T: Component>(&self) -> Option<&SparseSet<T>> {
+        self.sparse_sets.get(&T::component_id())?.as_any().downcast_ref()
+    }
+
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    /// Releases all per-frame scratch allocations of the arena
+    pub fn reset_scratch(&mut self) {
+        // Scratch memory is only reached through `arena()`, which the world borrow rules out
+        unsafe { self.arena.reset_scratch_unchecked() };
+    }
+
+    /// Makes the chunks of component `T` come from `allocator` instead of the arena
+    ///
+    /// Has to be called before the first `T` is inserted, returns false afterwards.
+    /// Chunks from other allocators are never moved by `compact_memory`.
+    pub fn set_chunk_allocator<T: Component>(&mut self, allocator: impl ChunkAllocator + 'static) -> bool {
+        if self.components.contains_key(&T::component_id()) {
+            return false;
+        }
+
+        self.chunk_allocators.insert(T::component_id(), Arc::new(allocator));
+        true
+    }
+
+    /// Compacts the arena once it is fragmented beyond `COMPACTION_THRESHOLD`
+    ///
+    /// Component chunks move towards the start of the arena regions and every storage
+    /// is pointed to its new memory. Chunks created from `arena()` outside the world
+    /// would move as well without being updated, so none may exist at this point.
+    /// Returns true if compaction ran.
+    pub fn compact_memory(&mut self) -> bool {
+        if !self.arena.needs_compaction() {
+            return false;
+        }
+
+        // The world is borrowed mutably, so no component reference can be alive
+        let relocations = unsafe { self.arena.compact() };
+
+        for archetype in &mut self.archetypes {
+            archetype.relocate(&relocations);
+        }
+
+        for sparse_set in self.sparse_sets.values_mut() {
+            sparse_set.relocate(&relocations);
+        }
+
+        true
+    }
+
+    fn sparse_set_mut_existing<T: Component>(&mut self) -> Option<&mut SparseSet<T>> {
+        self.sparse_sets.get_mut(&T::component_id())?.as_any_mut().downcast_mut()
+    }
+
+    fn sparse_set_mut<T: Component>(&mut self) -> &mut SparseSet<T> {
+        let allocator = self.chunk_allocator(T::component_id());
+        self.sparse_sets.entry(T::component_id())
+            .or_insert_with(|| Box::new(SparseSet::<T>::with_default_sizes(&allocator)))
+            .as_any_mut()
+            .downcast_mut()
+            .expect("Sparse set of a different type")
+    }
+
+    fn register_component<T: Component>(&mut self) {
+        self.components.entry(T::component_id())
+            .or_insert_with(ComponentInfo::of::<T>);
+    }
+
+    #[track_caller]
+    fn try_get_or_create_archetype(&mut self, components: ComponentMask) -> EcsResult<ArchetypeId> {
+        if let Some(&id) = self.archetype_index.get(&components) {
+            return Ok(id);
+        }
+
+        // The mask iterates in ascending order, as the archetype expects
+        let infos: Vec<ComponentInfo> = components.iter()
+            .map(|id| self.components[&id])
+            .collect();
+
+        let id = self.archetypes.len();
+        let entity_allocator: Arc<dyn ChunkAllocator> = self.arena.clone();
+        let archetype = Archetype::try_with_allocators(id, &entity_allocator, &infos, |info| {
+            self.chunk_allocator(info.id)
+        })?;
+        self.archetypes.push(archetype);
+        self.archetype_index.insert(components, id);
+        Ok(id)
+    }
+
+    /// Allocator of the component's chunks, the arena unless another one was set
+    fn chunk_allocator(&self, component_id: ComponentId) -> Arc<dyn ChunkAllocator> {
+        match self.chunk_allocators.get(&component_id) {
+            Some(allocator) => allocator.clone(),
+            None => self.arena.clone(),
+        }
+    }
+
+    /// Points the entity that swap_remove moved into `freed` at its new row
+    /// and compacts the archetype if the removal left it too sparse
+    fn relocate(&mut self, moved: Option<Entity>, freed: EntityLocation) {
+        if let Some(moved) = moved {
+            self.entities.set_location(moved.id, freed);
+        }
+
+        self.compact_archetype(freed.archetype_id);
+    }
+
+    /// Merges the sparse chunks of an archetype once `needs_compaction` says so
+    fn compact_archetype(&mut self, archetype_id: ArchetypeId) {
+        let archetype = &mut self.archetypes[archetype_id];
+        if !archetype.needs_compaction() {
+            return;
+        }
+
+        for (_, row) in archetype.compact() {
+            let entity = *archetype.entities().get(row).expect("Compacted row holds an entity");
+            self.entities.set_location(entity.id, EntityLocation { archetype_id, row });
+        }
+    }
+
+    /// Borrows two different archetypes mutably at once
+    fn archetype_pair(archetypes: &mut [Archetype], first: ArchetypeId, second: ArchetypeId) -> (&mut Archetype, &mut Archetype) {
+        assert_ne!(first, second, "Archetype pair must be two different archetypes");
+
+        if first < second {
+            let (left, right) = archetypes.split_at_mut(second);
+            (&mut left[first], &mut right[0])
+        } else {
+            let (left, right) = archetypes.split_at_mut(first);
+            (&mut right[0], &mut left[second])
+        }
+    }
+}
+
+impl Default for EcsMaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
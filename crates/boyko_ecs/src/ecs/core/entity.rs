@@ -1,5 +1,10 @@
+use crate::ecs::core::archetype::ArchetypeId;
+use crate::ecs::core::component::{Component, ComponentId, ComponentIdCell};
+use crate::ecs::memory::component_index::UnitId;
+
 pub type EntityId = u32;
 
+#[derive(Debug)]
 pub struct Entity {
     pub id: EntityId,
     pub generation: u16,
@@ -53,4 +58,147 @@ impl Clone for Entity {
     fn clone(&self) -> Self {
         *self
     }
-}
\ No newline at end of file
+}
+
+/// Entities are stored in archetype tables next to their components,
+/// so they can live in a regular `ComponentPool`
+impl Component for Entity {
+    #[inline]
+    fn component_id() -> ComponentId {
+        static ID: ComponentIdCell = ComponentIdCell::new();
+        ID.get_or_register::<Self>()
+    }
+
+    #[inline(always)]
+    fn name() -> &'static str {
+        "Entity"
+    }
+}
+
+/// Location of an entity's table components
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityLocation {
+    pub archetype_id: ArchetypeId,
+
+    /// Row of the entity, the same `UnitId` in every column of the archetype
+    pub row: UnitId,
+}
+
+impl EntityLocation {
+    /// Location of an entity that is not placed in any archetype yet
+    pub const INVALID: EntityLocation = EntityLocation {
+        archetype_id: ArchetypeId::MAX,
+        row: UnitId { id_chunk: u32::MAX, id_inland: u32::MAX },
+    };
+}
+
+/// Record of one entity ID in the entity index
+#[derive(Clone, Copy)]
+struct EntityMeta {
+    /// Current generation of the ID
+    generation: u16,
+
+    /// Whether the ID is currently in use
+    alive: bool,
+
+    location: EntityLocation,
+}
+
+/// Entity index: allocator of entity IDs and their locations
+///
+/// A dense table indexed by `EntityId` keeps the generation of every ID together
+/// with the archetype and row of the entity, so an entity's components are found
+/// in O(1). IDs of despawned entities are reused with an incremented generation,
+/// so stale `Entity` handles can be detected.
+pub struct Entities {
+    meta: Vec<EntityMeta>,
+
+    /// IDs released by despawned entities
+    free_ids: Vec<EntityId>,
+
+    /// Number of alive entities
+    count: usize,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self {
+            meta: Vec::new(),
+            free_ids: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// Allocates a new entity, reusing a free ID when possible
+    ///
+    /// The entity has `EntityLocation::INVALID` until `set_location` is called.
+    pub fn alloc(&mut self) -> Entity {
+        self.count += 1;
+
+        if let Some(id) = self.free_ids.pop() {
+            let meta = &mut self.meta[id as usize];
+            meta.alive = true;
+            meta.location = EntityLocation::INVALID;
+            return Entity::new(id, meta.generation);
+        }
+
+        let id = self.meta.len() as EntityId;
+        self.meta.push(EntityMeta {
+            generation: 0,
+            alive: true,
+            location: EntityLocation::INVALID,
+        });
+        Entity::with_id(id)
+    }
+
+    /// Releases the entity's ID and returns its last location,
+    /// `None` if the entity was already dead
+    pub fn free(&mut self, entity: Entity) -> Option<EntityLocation> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let meta = &mut self.meta[entity.id as usize];
+        meta.alive = false;
+        meta.generation = meta.generation.wrapping_add(1);
+        self.free_ids.push(entity.id);
+        self.count -= 1;
+        Some(meta.location)
+    }
+
+    #[inline]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.meta.get(entity.id as usize)
+            .is_some_and(|meta| meta.alive && meta.generation == entity.generation)
+    }
+
+    /// Gets the location of an alive entity
+    #[inline]
+    pub fn location(&self, entity: Entity) -> Option<EntityLocation> {
+        let meta = self.meta.get(entity.id as usize)?;
+        (meta.alive && meta.generation == entity.generation).then_some(meta.location)
+    }
+
+    /// Updates the location of an alive entity, e.g. after swap_remove moved it
+    #[inline]
+    pub fn set_location(&mut self, id: EntityId, location: EntityLocation) {
+        let meta = &mut self.meta[id as usize];
+        debug_assert!(meta.alive, "Setting the location of a dead entity");
+        meta.location = location;
+    }
+
+    /// Number of alive entities
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+impl Default for Entities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
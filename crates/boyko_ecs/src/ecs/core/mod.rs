@@ -1,5 +1,6 @@
 pub mod archetype;
 pub mod component;
+pub mod component_mask;
 pub mod ecs_master;
 pub mod entity;
-
+pub mod query;
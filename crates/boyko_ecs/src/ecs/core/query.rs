@@ -0,0 +1,398 @@
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use crate::ecs::core::archetype::{Archetype, ArchetypeId};
+use crate::ecs::core::component::{Component, ComponentId, StorageType};
+use crate::ecs::core::component_mask::ComponentMask;
+use crate::ecs::core::ecs_master::EcsMaster;
+use crate::ecs::core::entity::Entity;
+use crate::ecs::memory::component_index::UnitId;
+use crate::ecs::memory::component_pool::ComponentPool;
+use crate::ecs::memory::sparse_set::SparseSet;
+
+/// Component requirements collected from the query data and filter
+///
+/// Only table components take part in archetype matching, sparse components
+/// are checked per entity while iterating.
+#[derive(Default)]
+pub struct QueryAccess {
+    /// Table components an archetype must contain
+    with: ComponentMask,
+
+    /// Table components an archetype must not contain
+    without: ComponentMask,
+
+    /// Components read or written by the query, `true` for mutable access
+    access: Vec<(ComponentId, &'static str, bool)>,
+}
+
+impl QueryAccess {
+    /// Requires archetypes to contain the component
+    pub fn add_with(&mut self, component_id: ComponentId) {
+        self.with.insert(component_id);
+    }
+
+    /// Requires archetypes to not contain the component
+    pub fn add_without(&mut self, component_id: ComponentId) {
+        self.without.insert(component_id);
+    }
+
+    /// Registers a read or write of the component
+    ///
+    /// Panics if the query would hand out aliasing references to the same component.
+    pub fn add_access(&mut self, component_id: ComponentId, name: &'static str, mutable: bool) {
+        let conflict = self.access.iter()
+            .any(|&(id, _, other_mutable)| id == component_id && (mutable || other_mutable));
+        assert!(!conflict, "Query has conflicting access to component {name}");
+
+        self.access.push((component_id, name, mutable));
+    }
+
+    /// Checks if the archetype's table components satisfy the query
+    #[inline]
+    pub fn matches(&self, archetype: &Archetype) -> bool {
+        self.matches_mask(archetype.mask())
+    }
+
+    /// Checks if a component set satisfies the query
+    #[inline]
+    pub fn matches_mask(&self, components: &ComponentMask) -> bool {
+        components.contains_all(&self.with) && !components.intersects(&self.without)
+    }
+}
+
+/// Data fetched by a query for every matching entity
+///
+/// # Safety
+/// `update_access` must register every component the fetch reads or writes,
+/// so conflicting mutable access is rejected before any item is produced.
+pub unsafe trait QueryData {
+    type Item<'w>;
+
+    /// Per-archetype state, prepared once before iterating the archetype's rows
+    type Fetch<'w>;
+
+    fn update_access(access: &mut QueryAccess);
+
+    fn init_fetch<'w>(master: &'w EcsMaster, archetype: &'w Archetype) -> Self::Fetch<'w>;
+
+    /// Fetches the item for the entity stored at `row`
+    ///
+    /// # Safety
+    /// Each row may be fetched only once per iteration, and the master must not
+    /// be accessed by anything else while the items are alive.
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity, row: UnitId) -> Option<Self::Item<'w>>;
+}
+
+/// Filter deciding which entities a query visits, without fetching data
+pub trait QueryFilter {
+    type Fetch<'w>;
+
+    fn update_access(access: &mut QueryAccess);
+
+    fn init_fetch<'w>(master: &'w EcsMaster, archetype: &'w Archetype) -> Self::Fetch<'w>;
+
+    fn matches(fetch: &mut Self::Fetch<'_>, entity: Entity) -> bool;
+}
+
+/// Per-archetype fetch of a component, either from the table or from the sparse set
+pub enum ComponentFetch<'w, T: Component> {
+    Table(Option<&'w ComponentPool<T>>),
+    Sparse(Option<&'w SparseSet<T>>),
+
+    /// Zero-sized table component, present if the archetype has the marker
+    Marker(Option<NonNull<T>>),
+}
+
+impl<'w, T: Component> ComponentFetch<'w, T> {
+    fn new(master: &'w EcsMaster, archetype: &'w Archetype) -> Self {
+        match T::storage_type() {
+            StorageType::Table if T::size() == 0 => Self::Marker(archetype.marker_ptr::<T>()),
+            StorageType::Table => Self::Table(archetype.pool::<T>()),
+            StorageType::Sparse => Self::Sparse(master.sparse_set::<T>()),
+        }
+    }
+
+    #[inline]
+    fn get_ptr(&self, entity: Entity, row: UnitId) -> Option<NonNull<T>> {
+        match self {
+            Self::Table(pool) => pool.and_then(|pool| pool.get_ptr(row)),
+            Self::Sparse(set) => set.and_then(|set| set.get_ptr(entity.id)),
+            Self::Marker(marker) => *marker,
+        }
+    }
+}
+
+unsafe impl QueryData for Entity {
+    type Item<'w> = Entity;
+    type Fetch<'w> = ();
+
+    fn update_access(_access: &mut QueryAccess) {}
+
+    fn init_fetch<'w>(_master: &'w EcsMaster, _archetype: &'w Archetype) -> Self::Fetch<'w> {}
+
+    #[inline]
+    unsafe fn fetch<'w>(_fetch: &mut Self::Fetch<'w>, entity: Entity, _row: UnitId) -> Option<Self::Item<'w>> {
+        Some(entity)
+    }
+}
+
+unsafe impl<T: Component> QueryData for &T {
+    type Item<'w> = &'w T;
+    type Fetch<'w> = ComponentFetch<'w, T>;
+
+    fn update_access(access: &mut QueryAccess) {
+        if T::storage_type() == StorageType::Table {
+            access.add_with(T::component_id());
+        }
+        access.add_access(T::component_id(), T::name(), false);
+    }
+
+    fn init_fetch<'w>(master: &'w EcsMaster, archetype: &'w Archetype) -> Self::Fetch<'w> {
+        ComponentFetch::new(master, archetype)
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity, row: UnitId) -> Option<Self::Item<'w>> {
+        fetch.get_ptr(entity, row).map(|ptr| unsafe { &*ptr.as_ptr() })
+    }
+}
+
+unsafe impl<T: Component> QueryData for &mut T {
+    type Item<'w> = &'w mut T;
+    type Fetch<'w> = ComponentFetch<'w, T>;
+
+    fn update_access(access: &mut QueryAccess) {
+        if T::storage_type() == StorageType::Table {
+            access.add_with(T::component_id());
+        }
+        access.add_access(T::component_id(), T::name(), true);
+    }
+
+    fn init_fetch<'w>(master: &'w EcsMaster, archetype: &'w Archetype) -> Self::Fetch<'w> {
+        ComponentFetch::new(master, archetype)
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity, row: UnitId) -> Option<Self::Item<'w>> {
+        // The pointer comes from arena memory, not from the shared pool reference,
+        // and access checks guarantee nobody else touches this component
+        fetch.get_ptr(entity, row).map(|ptr| unsafe { &mut *ptr.as_ptr() })
+    }
+}
+
+/// Filter for entities that have the component
+pub struct With<T: Component>(PhantomData<T>);
+
+/// Filter for entities that do not have the component
+pub struct Without<T: Component>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for With<T> {
+    type Fetch<'w> = Option<&'w SparseSet<T>>;
+
+    fn update_access(access: &mut QueryAccess) {
+        if T::storage_type() == StorageType::Table {
+            access.add_with(T::component_id());
+        }
+    }
+
+    fn init_fetch<'w>(master: &'w EcsMaster, _archetype: &'w Archetype) -> Self::Fetch<'w> {
+        match T::storage_type() {
+            StorageType::Table => None,
+            StorageType::Sparse => master.sparse_set::<T>(),
+        }
+    }
+
+    #[inline]
+    fn matches(fetch: &mut Self::Fetch<'_>, entity: Entity) -> bool {
+        match T::storage_type() {
+            StorageType::Table => true,
+            StorageType::Sparse => fetch.is_some_and(|set| set.contains(entity.id)),
+        }
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    type Fetch<'w> = Option<&'w SparseSet<T>>;
+
+    fn update_access(access: &mut QueryAccess) {
+        if T::storage_type() == StorageType::Table {
+            access.add_without(T::component_id());
+        }
+    }
+
+    fn init_fetch<'w>(master: &'w EcsMaster, _archetype: &'w Archetype) -> Self::Fetch<'w> {
+        match T::storage_type() {
+            StorageType::Table => None,
+            StorageType::Sparse => master.sparse_set::<T>(),
+        }
+    }
+
+    #[inline]
+    fn matches(fetch: &mut Self::Fetch<'_>, entity: Entity) -> bool {
+        match T::storage_type() {
+            StorageType::Table => true,
+            StorageType::Sparse => !fetch.is_some_and(|set| set.contains(entity.id)),
+        }
+    }
+}
+
+impl QueryFilter for () {
+    type Fetch<'w> = ();
+
+    fn update_access(_access: &mut QueryAccess) {}
+
+    fn init_fetch<'w>(_master: &'w EcsMaster, _archetype: &'w Archetype) -> Self::Fetch<'w> {}
+
+    #[inline]
+    fn matches(_fetch: &mut Self::Fetch<'_>, _entity: Entity) -> bool {
+        true
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        unsafe impl<$($name: QueryData),+> QueryData for ($($name,)+) {
+            type Item<'w> = ($($name::Item<'w>,)+);
+            type Fetch<'w> = ($($name::Fetch<'w>,)+);
+
+            fn update_access(access: &mut QueryAccess) {
+                $($name::update_access(access);)+
+            }
+
+            fn init_fetch<'w>(master: &'w EcsMaster, archetype: &'w Archetype) -> Self::Fetch<'w> {
+                ($($name::init_fetch(master, archetype),)+)
+            }
+
+            #[inline]
+            unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity, row: UnitId) -> Option<Self::Item<'w>> {
+                let ($($name,)+) = fetch;
+                Some(($(unsafe { $name::fetch($name, entity, row)? },)+))
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),+> QueryFilter for ($($name,)+) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)+);
+
+            fn update_access(access: &mut QueryAccess) {
+                $($name::update_access(access);)+
+            }
+
+            fn init_fetch<'w>(master: &'w EcsMaster, archetype: &'w Archetype) -> Self::Fetch<'w> {
+                ($($name::init_fetch(master, archetype),)+)
+            }
+
+            #[inline]
+            fn matches(fetch: &mut Self::Fetch<'_>, entity: Entity) -> bool {
+                let ($($name,)+) = fetch;
+                $($name::matches($name, entity))&&+
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+
+/// Iterator over the entities matching a query
+///
+/// Walks the matching archetypes chunk by chunk, so table components are
+/// visited in memory order.
+pub struct QueryIter<'w, Q: QueryData, F: QueryFilter = ()> {
+    master: &'w EcsMaster,
+
+    /// Archetypes whose table components satisfy the query
+    archetypes: Vec<ArchetypeId>,
+
+    /// Position in `archetypes` of the archetype being iterated
+    archetype_index: usize,
+
+    /// Fetch state of the archetype being iterated
+    fetch: Option<(&'w Archetype, Q::Fetch<'w>, F::Fetch<'w>)>,
+
+    chunk_index: usize,
+    inland_index: usize,
+
+    /// The iterator hands out items borrowed from the exclusively borrowed master
+    _marker: PhantomData<&'w mut EcsMaster>,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> QueryIter<'w, Q, F> {
+    pub(crate) fn new(master: &'w mut EcsMaster) -> Self {
+        let mut access = QueryAccess::default();
+        Q::update_access(&mut access);
+        F::update_access(&mut access);
+
+        let master = &*master;
+        let archetypes = master.archetypes().iter()
+            .filter(|archetype| !archetype.is_empty() && access.matches(archetype))
+            .map(|archetype| archetype.id())
+            .collect();
+
+        Self {
+            master,
+            archetypes,
+            archetype_index: 0,
+            fetch: None,
+            chunk_index: 0,
+            inland_index: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((archetype, query_fetch, filter_fetch)) = &mut self.fetch else {
+                let archetype_id = *self.archetypes.get(self.archetype_index)?;
+                let archetype = self.master.archetype(archetype_id)?;
+
+                self.fetch = Some((
+                    archetype,
+                    Q::init_fetch(self.master, archetype),
+                    F::init_fetch(self.master, archetype),
+                ));
+                self.chunk_index = 0;
+                self.inland_index = 0;
+                continue;
+            };
+
+            let entities = archetype.entities();
+            if self.chunk_index >= entities.chunks_count() {
+                self.fetch = None;
+                self.archetype_index += 1;
+                continue;
+            }
+
+            let chunk = entities.chunk_components(self.chunk_index).unwrap_or(&[]);
+            if self.inland_index >= chunk.len() {
+                self.chunk_index += 1;
+                self.inland_index = 0;
+                continue;
+            }
+
+            let entity = chunk[self.inland_index];
+            let row = UnitId::new(self.chunk_index, self.inland_index);
+            self.inland_index += 1;
+
+            if !F::matches(filter_fetch, entity) {
+                continue;
+            }
+
+            // Safety: every row is visited once and the master is borrowed exclusively for 'w
+            if let Some(item) = unsafe { Q::fetch(query_fetch, entity, row) } {
+                return Some(item);
+            }
+        }
+    }
+}
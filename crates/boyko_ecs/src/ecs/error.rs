@@ -0,0 +1,56 @@
+use std::alloc::LayoutError;
+use std::fmt;
+use crate::ecs::core::entity::Entity;
+
+/// Errors reported by the fallible (`try_`) ECS and memory APIs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcsError {
+    /// The arena has no room for the allocation and is not allowed to grow further
+    OutOfMemory { size: usize, align: usize },
+
+    /// The requested size and alignment do not form a valid layout
+    InvalidLayout,
+
+    /// The entity was despawned or its generation is outdated
+    StaleEntity(Entity),
+
+    /// The entity or slot does not have the component
+    MissingComponent(&'static str),
+
+    /// A fixed-size storage has no free slot left
+    CapacityExceeded { capacity: usize },
+
+    /// Canary bytes around an arena allocation were overwritten
+    MemoryCorruption { address: usize },
+}
+
+pub type EcsResult<T> = Result<T, EcsError>;
+
+impl fmt::Display for EcsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EcsError::OutOfMemory { size, align } => {
+                write!(f, "arena out of memory: cannot allocate {size} bytes aligned to {align}")
+            }
+            EcsError::InvalidLayout => write!(f, "invalid memory layout"),
+            EcsError::StaleEntity(entity) => {
+                write!(f, "entity {}v{} is not alive", entity.id, entity.generation)
+            }
+            EcsError::MissingComponent(name) => write!(f, "component {name} is missing"),
+            EcsError::CapacityExceeded { capacity } => {
+                write!(f, "storage capacity of {capacity} components exceeded")
+            }
+            EcsError::MemoryCorruption { address } => {
+                write!(f, "arena allocation at {address:#x} was written out of bounds")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EcsError {}
+
+impl From<LayoutError> for EcsError {
+    fn from(_: LayoutError) -> Self {
+        EcsError::InvalidLayout
+    }
+}
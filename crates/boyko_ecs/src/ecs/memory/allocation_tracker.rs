@@ -0,0 +1,193 @@
+use std::fmt;
+use std::panic::Location;
+#[cfg(any(debug_assertions, feature = "debug-arena"))]
+use std::collections::HashMap;
+#[cfg(any(debug_assertions, feature = "debug-arena"))]
+use std::sync::Mutex;
+use crate::ecs::core::archetype::ArchetypeId;
+use crate::ecs::core::component::ComponentId;
+
+/// Storage a chunk belongs to, so leaked chunks can be told apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PoolTag {
+    /// Pool or chunk created on its own, outside of a world
+    #[default]
+    Standalone,
+
+    /// Entity column of an archetype
+    Entities { archetype: ArchetypeId },
+
+    /// Component column of an archetype
+    Column { archetype: ArchetypeId, component: ComponentId },
+
+    /// Sparse set of a component
+    SparseSet { component: ComponentId },
+}
+
+impl fmt::Display for PoolTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolTag::Standalone => write!(f, "standalone pool"),
+            PoolTag::Entities { archetype } => write!(f, "entity column of archetype {archetype}"),
+            PoolTag::Column { archetype, component } => write!(f, "column {component} of archetype {archetype}"),
+            PoolTag::SparseSet { component } => write!(f, "sparse set of component {component}"),
+        }
+    }
+}
+
+/// Live arena allocation recorded in debug builds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationRecord {
+    pub address: usize,
+
+    pub size: usize,
+
+    pub align: usize,
+
+    /// Owner tag, e.g. the component type of a chunk
+    pub owner: Option<&'static str>,
+
+    /// Storage of a component chunk, `None` for other allocations
+    pub pool: Option<PoolTag>,
+
+    /// Code that requested the allocation
+    pub location: &'static Location<'static>,
+}
+
+impl fmt::Display for AllocationRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes (align {}) at {:#x}", self.size, self.align, self.address)?;
+
+        if let Some(owner) = self.owner {
+            write!(f, " owned by {owner}")?;
+        }
+
+        if let Some(pool) = self.pool {
+            write!(f, " in {pool}")?;
+        }
+
+        write!(f, ", allocated at {}", self.location)
+    }
+}
+
+/// Allocations that were never freed, grouped by allocation site
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    pub allocations: Vec<AllocationRecord>,
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.allocations.is_empty()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.allocations.iter().map(|record| record.size).sum()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} arena allocations ({} bytes) were never freed", self.allocations.len(), self.total_bytes())?;
+
+        for record in &self.allocations {
+            write!(f, "\n  {record}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Called with the leak report of an arena dropped while allocations were still live
+pub type LeakHook = fn(&LeakReport);
+
+/// Leak hook that writes the report to stderr
+pub fn print_leaks(report: &LeakReport) {
+    eprintln!("{report}");
+}
+
+/// Registry of live allocations, only filled in debug builds or with the `debug-arena` feature
+///
+/// Other builds keep no records, so tracking costs nothing there.
+pub(crate) struct AllocationTracker {
+    #[cfg(any(debug_assertions, feature = "debug-arena"))]
+    records: Mutex<HashMap<usize, AllocationRecord>>,
+}
+
+impl AllocationTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            #[cfg(any(debug_assertions, feature = "debug-arena"))]
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[cfg_attr(not(any(debug_assertions, feature = "debug-arena")), allow(unused_variables))]
+    pub(crate) fn record(&self, record: AllocationRecord) {
+        #[cfg(any(debug_assertions, feature = "debug-arena"))]
+        self.records().insert(record.address, record);
+    }
+
+    #[cfg_attr(not(any(debug_assertions, feature = "debug-arena")), allow(unused_variables))]
+    pub(crate) fn remove(&self, address: usize) {
+        #[cfg(any(debug_assertions, feature = "debug-arena"))]
+        self.records().remove(&address);
+    }
+
+    #[cfg_attr(not(any(debug_assertions, feature = "debug-arena")), allow(unused_variables))]
+    pub(crate) fn resize(&self, address: usize, size: usize) {
+        #[cfg(any(debug_assertions, feature = "debug-arena"))]
+        if let Some(record) = self.records().get_mut(&address) {
+            record.size = size;
+        }
+    }
+
+    /// Attaches the storage a chunk belongs to
+    #[cfg_attr(not(any(debug_assertions, feature = "debug-arena")), allow(unused_variables))]
+    pub(crate) fn set_pool(&self, address: usize, pool: PoolTag) {
+        #[cfg(any(debug_assertions, feature = "debug-arena"))]
+        if let Some(record) = self.records().get_mut(&address) {
+            record.pool = Some(pool);
+        }
+    }
+
+    /// Moves the record of an allocation that was relocated
+    #[cfg_attr(not(any(debug_assertions, feature = "debug-arena")), allow(unused_variables))]
+    pub(crate) fn relocate(&self, old_address: usize, new_address: usize) {
+        #[cfg(any(debug_assertions, feature = "debug-arena"))]
+        {
+            let mut records = self.records();
+            if let Some(mut record) = records.remove(&old_address) {
+                record.address = new_address;
+                records.insert(new_address, record);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        #[cfg(any(debug_assertions, feature = "debug-arena"))]
+        self.records().clear();
+    }
+
+    #[cfg(feature = "debug-arena")]
+    pub(crate) fn get(&self, address: usize) -> Option<AllocationRecord> {
+        self.records().get(&address).copied()
+    }
+
+    pub(crate) fn report(&self) -> LeakReport {
+        #[cfg(any(debug_assertions, feature = "debug-arena"))]
+        {
+            let mut allocations: Vec<_> = self.records().values().copied().collect();
+            allocations.sort_unstable_by_key(|record| (record.location.file(), record.location.line(), record.address));
+            LeakReport { allocations }
+        }
+
+        #[cfg(not(any(debug_assertions, feature = "debug-arena")))]
+        LeakReport::default()
+    }
+
+    #[cfg(any(debug_assertions, feature = "debug-arena"))]
+    fn records(&self) -> std::sync::MutexGuard<'_, HashMap<usize, AllocationRecord>> {
+        self.records.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,56 @@
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use allocator_api2::alloc::{AllocError, Allocator};
+use crate::ecs::memory::arena::Arena;
+
+/// Lets `allocator_api2` collections live in an arena, e.g. `Vec::new_in(&arena)`
+///
+/// `grow` and `shrink` resize in place whenever the arena allows it and only
+/// fall back to allocate, copy and free otherwise.
+unsafe impl Allocator for &Arena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.try_allocate_layout(layout).map_err(|_| AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        Arena::deallocate(self, ptr, layout);
+    }
+
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(new_layout.size() >= old_layout.size());
+        unsafe { reallocate(self, ptr, old_layout, new_layout, old_layout.size()) }
+    }
+
+    unsafe fn grow_zeroed(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = unsafe { self.grow(ptr, old_layout, new_layout)? };
+
+        unsafe {
+            new_ptr.cast::<u8>().as_ptr().add(old_layout.size())
+                .write_bytes(0, new_layout.size() - old_layout.size());
+        }
+
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(new_layout.size() <= old_layout.size());
+        unsafe { reallocate(self, ptr, old_layout, new_layout, new_layout.size()) }
+    }
+}
+
+/// Resizes in place if possible, otherwise moves the first `keep` bytes to a new block
+unsafe fn reallocate(arena: &Arena, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout, keep: usize) -> Result<NonNull<[u8]>, AllocError> {
+    if arena.resize_in_place(ptr, old_layout, new_layout) {
+        return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+    }
+
+    let new_ptr = Allocator::allocate(&arena, new_layout)?;
+
+    unsafe {
+        ptr.as_ptr().copy_to_nonoverlapping(new_ptr.cast::<u8>().as_ptr(), keep);
+        arena.deallocate(ptr, old_layout);
+    }
+
+    Ok(new_ptr)
+}
@@ -1,39 +1,624 @@
-use std::alloc::{alloc, Layout};
-use std::cell::UnsafeCell;
+use std::alloc::{alloc, dealloc, Layout};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::panic::Location;
 use std::ptr::NonNull;
-use crate::ecs::constants::{CACHE_LINE_SIZE, DEFAULT_ARENA_SIZE};
-use crate::ecs::memory::free_mem_block::MemFreeBlockMaster;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use crate::ecs::constants::{
+    CACHE_LINE_SIZE,
+    COMPACTION_THRESHOLD,
+    DEFAULT_ARENA_SIZE,
+    DEFAULT_SCRATCH_SIZE,
+    GROWTH_FACTOR,
+    MAX_EXPANSION_FACTOR,
+    MIN_BLOCK_ALIGN,
+    THREAD_CACHE_STRIPES,
+    THREAD_CACHE_MIN_BLOCK_SIZE,
+    THREAD_CACHE_MAX_BLOCK_SIZE,
+    THREAD_CACHE_REFILL_BLOCKS,
+    THREAD_CACHE_MAX_BLOCKS,
+};
+use crate::ecs::error::{EcsError, EcsResult};
+use crate::ecs::memory::allocation_tracker::{AllocationRecord, AllocationTracker, LeakHook, LeakReport, PoolTag};
+use crate::ecs::memory::arena_stats::{ArenaCounters, ArenaStats};
+use crate::ecs::memory::free_mem_block::{FreeBlockPolicy, MemFreeBlock, MemFreeBlockMaster};
+#[cfg(feature = "debug-arena")]
+use crate::ecs::memory::debug_arena::DebugArena;
+#[cfg(target_os = "linux")]
+use crate::ecs::memory::mmap::{HugePages, MmapRegion};
+use crate::ecs::memory::scratch::{ScratchRegion, ScratchScope};
+use crate::ecs::memory::sub_arena::SubArena;
+use crate::ecs::memory::tlsf::TlsfFreeBlocks;
 use crate::ecs::memory::utils::align_up;
 
-pub struct Arena {
+/// Number of size classes between the smallest and the largest cached block size
+const SIZE_CLASSES: usize = (THREAD_CACHE_MAX_BLOCK_SIZE.trailing_zeros()
+    - THREAD_CACHE_MIN_BLOCK_SIZE.trailing_zeros()) as usize + 1;
+
+/// Where a region gets its memory from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArenaBackend {
+    /// Regions are allocated with `std::alloc::alloc`
+    #[default]
+    System,
+
+    /// One region reserving `max_capacity` bytes of address space with `mmap`,
+    /// committed page by page as blocks are handed out
+    #[cfg(target_os = "linux")]
+    Mmap(HugePages),
+}
+
+/// How a region keeps track of its free space
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FreeBlockStrategy {
+    /// Best fit over a size-ordered tree of free blocks
+    #[default]
+    BestFit,
+
+    /// Two-Level Segregated Fit, bounded-time allocate and free
+    Tlsf,
+}
+
+/// Settings of a new arena
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaConfig {
+    /// Size of the first region in bytes
+    pub capacity: usize,
+
+    /// Upper bound for the total size of all regions
+    pub max_capacity: usize,
+
+    /// Size of the scratch region in bytes
+    pub scratch_capacity: usize,
+
+    pub backend: ArenaBackend,
+
+    pub free_blocks: FreeBlockStrategy,
+
+    /// Receives the leak report when the arena is dropped, e.g. `print_leaks`
+    ///
+    /// Leaks are only tracked in debug builds or with the `debug-arena` feature.
+    pub leak_hook: Option<LeakHook>,
+
+    /// Places large allocations right before an inaccessible page, only on Linux
+    #[cfg(feature = "debug-arena")]
+    pub guard_pages: bool,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_ARENA_SIZE,
+            max_capacity: DEFAULT_ARENA_SIZE.saturating_mul(MAX_EXPANSION_FACTOR),
+            scratch_capacity: DEFAULT_SCRATCH_SIZE,
+            backend: ArenaBackend::System,
+            free_blocks: FreeBlockStrategy::BestFit,
+            leak_hook: None,
+            #[cfg(feature = "debug-arena")]
+            guard_pages: false,
+        }
+    }
+}
+
+/// Allocation moved by `Arena::compact`
+#[derive(Debug, Default)]
+pub struct Relocations {
+    /// New addresses by old address
+    moves: HashMap<usize, NonNull<u8>>,
+}
+
+impl Relocations {
+    /// New address of an allocation, `None` if it stayed where it was
+    #[inline]
+    pub fn new_address(&self, old: NonNull<u8>) -> Option<NonNull<u8>> {
+        self.moves.get(&(old.as_ptr() as usize)).copied()
+    }
+
+    /// Number of moved allocations
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+}
+
+/// Block that `Arena::compact` is allowed to move
+#[derive(Clone, Copy)]
+struct RelocatableBlock {
+    layout: Layout,
+
+    /// Offset of the owner's pointer inside the block, non-zero with debug canaries
+    offset: usize,
+}
+
+/// Memory behind an arena region
+enum RegionMemory {
+    /// Heap allocation with the given layout
+    System(Layout),
+
+    /// Reserved address range, committed lazily
+    #[cfg(target_os = "linux")]
+    Mmap(MmapRegion),
+
+    /// Block of a parent arena, given back by the `SubArena` that owns it
+    Parent,
+}
+
+/// One contiguous backing allocation of an arena with its own free blocks
+///
+/// Blocks are handed out in whole `MIN_BLOCK_ALIGN` granules, so every free block
+/// starts at a multiple of it.
+struct ArenaRegion {
     ptr: NonNull<u8>,
 
     capacity: usize,
 
-    cursor: UnsafeCell<usize>,
+    memory: RegionMemory,
 
-    layout: Layout,
+    free_blocks: Box<dyn FreeBlockPolicy>,
+}
+
+impl ArenaRegion {
+    fn new(capacity: usize, backend: ArenaBackend, strategy: FreeBlockStrategy) -> EcsResult<Self> {
+        let (ptr, capacity, memory) = match backend {
+            ArenaBackend::System => {
+                let aligned_capacity = align_up(capacity, CACHE_LINE_SIZE);
+                let layout = Layout::from_size_align(aligned_capacity, CACHE_LINE_SIZE)?;
+
+                let ptr = NonNull::new(unsafe { alloc(layout) })
+                    .ok_or(EcsError::OutOfMemory { size: layout.size(), align: layout.align() })?;
+
+                (ptr, aligned_capacity, RegionMemory::System(layout))
+            }
+            #[cfg(target_os = "linux")]
+            ArenaBackend::Mmap(huge_pages) => {
+                let mmap = MmapRegion::reserve(capacity, huge_pages)?;
+                (mmap.as_ptr(), mmap.reserved(), RegionMemory::Mmap(mmap))
+            }
+        };
+
+        let free_blocks: Box<dyn FreeBlockPolicy> = match strategy {
+            FreeBlockStrategy::BestFit => Box::new(MemFreeBlockMaster::new_init(capacity)),
+            FreeBlockStrategy::Tlsf => Box::new(TlsfFreeBlocks::new_init(capacity)),
+        };
+
+        Ok(Self { ptr, capacity, memory, free_blocks })
+    }
+
+    /// Region over a block of a parent arena, always tracked by `MemFreeBlockMaster`
+    fn in_parent_block(ptr: NonNull<u8>, capacity: usize) -> Self {
+        Self {
+            ptr,
+            capacity,
+            memory: RegionMemory::Parent,
+            free_blocks: Box::new(MemFreeBlockMaster::new_init(capacity)),
+        }
+    }
+
+    /// Empty free block tracking of the strategy
+    fn empty_free_blocks(strategy: FreeBlockStrategy) -> Box<dyn FreeBlockPolicy> {
+        match strategy {
+            FreeBlockStrategy::BestFit => Box::new(MemFreeBlockMaster::new()),
+            FreeBlockStrategy::Tlsf => Box::new(TlsfFreeBlocks::new()),
+        }
+    }
+
+    /// Size of the block holding `size` bytes
+    #[inline(always)]
+    fn block_size(size: usize) -> usize {
+        align_up(size, MIN_BLOCK_ALIGN)
+    }
+
+    /// Share of the free space outside the largest free block
+    fn fragmentation(&self) -> f32 {
+        ArenaStats::fragmentation(self.free_blocks.total_free_size(), self.free_blocks.largest_free_block())
+    }
+
+    /// Slides the relocatable blocks of the region towards its start
+    ///
+    /// `blocks` are offsets and layouts sorted by offset. Each block moves to the lowest
+    /// free space behind the previously moved block that fits it, which is never above
+    /// its current place, so copying in ascending order never overwrites a block that has
+    /// not moved yet. Holes skipped on the way stay free, so every block is placed in one
+    /// scan from the cursor. The free space left over is rebuilt into fresh free blocks.
+    /// Returns the new offsets.
+    fn compact(&mut self, blocks: &[(usize, Layout)], strategy: FreeBlockStrategy) -> Vec<usize> {
+        let base = self.ptr.as_ptr() as usize;
+        let mut free: BTreeMap<usize, usize> = self.free_blocks.blocks().into_iter()
+            .map(|block| (block.start, block.end))
+            .collect();
+
+        // End of the last moved block, free ranges below it are never looked at again.
+        // The last moved block occupies the space right below the cursor, so the range
+        // a block frees never merges below it and the block always fits its own place.
+        let mut cursor = 0;
+        let mut targets = Vec::with_capacity(blocks.len());
+        for &(start, layout) in blocks {
+            let size = Self::block_size(layout.size());
+            Self::insert_merged(&mut free, start, start + size);
+
+            let (free_start, free_end, target) = free.range(cursor..)
+                .find_map(|(&free_start, &free_end)| {
+                    let target = align_up(base + free_start, layout.align()) - base;
+                    (target + size <= free_end).then_some((free_start, free_end, target))
+                })
+                .expect("The block always fits into its own place");
+
+            free.remove(&free_start);
+            if target > free_start {
+                free.insert(free_start, target);
+            }
+            if target + size < free_end {
+                free.insert(target + size, free_end);
+            }
+
+            if target != start {
+                unsafe {
+                    std::ptr::copy(self.ptr.as_ptr().add(start), self.ptr.as_ptr().add(target), layout.size());
+                }
+            }
+
+            cursor = target + size;
+            targets.push(target);
+        }
+
+        self.free_blocks = Self::empty_free_blocks(strategy);
+        for (start, end) in free {
+            self.free_blocks.insert(MemFreeBlock::new(start, end));
+            self.release(start, end);
+        }
+
+        targets
+    }
+
+    /// Adds a free range to a start-ordered map, merging it with its neighbours
+    fn insert_merged(free: &mut BTreeMap<usize, usize>, mut start: usize, mut end: usize) {
+        if let Some((&previous_start, &previous_end)) = free.range(..start).next_back()
+            && previous_end == start
+        {
+            free.remove(&previous_start);
+            start = previous_start;
+        }
+
+        if let Some(next_end) = free.remove(&end) {
+            end = next_end;
+        }
+
+        free.insert(start, end);
+    }
+
+    /// Offset of `ptr` inside the region, if it belongs to it
+    #[inline]
+    fn offset_of(&self, ptr: NonNull<u8>) -> Option<usize> {
+        let offset = (ptr.as_ptr() as usize).checked_sub(self.ptr.as_ptr() as usize)?;
+        (offset < self.capacity).then_some(offset)
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let size = Self::block_size(layout.size());
+        let block = self.free_blocks.allocate_aligned(size, layout.align(), self.ptr.as_ptr() as usize)?;
+
+        if !self.commit(block.end) {
+            self.free_blocks.insert(block);
+            return None;
+        }
+
+        NonNull::new(unsafe { self.ptr.as_ptr().add(block.start) })
+    }
+
+    /// Makes the memory below `end` usable
+    #[inline]
+    fn commit(&mut self, end: usize) -> bool {
+        match &mut self.memory {
+            RegionMemory::System(_) | RegionMemory::Parent => true,
+            #[cfg(target_os = "linux")]
+            RegionMemory::Mmap(mmap) => mmap.commit_to(end),
+        }
+    }
+
+    /// Lets the backend reclaim the pages of a freed block
+    #[inline]
+    fn release(&self, start: usize, end: usize) {
+        match &self.memory {
+            RegionMemory::System(_) | RegionMemory::Parent => {}
+            #[cfg(target_os = "linux")]
+            RegionMemory::Mmap(mmap) => mmap.release(start, end),
+        }
+    }
+
+    /// Number of bytes actually backed by memory
+    fn committed(&self) -> usize {
+        match &self.memory {
+            RegionMemory::System(_) | RegionMemory::Parent => self.capacity,
+            #[cfg(target_os = "linux")]
+            RegionMemory::Mmap(mmap) => mmap.committed(),
+        }
+    }
+}
+
+impl Drop for ArenaRegion {
+    fn drop(&mut self) {
+        // Mapped memory is unmapped by the mmap region itself, parent blocks by their sub-arena
+        if let RegionMemory::System(layout) = self.memory {
+            unsafe {
+                dealloc(self.ptr.as_ptr(), layout);
+            }
+        }
+    }
+}
+
+/// Regions and growth limit, shared by all threads behind the arena lock
+struct ArenaState {
+    regions: Vec<ArenaRegion>,
 
-    free_blocks: UnsafeCell<MemFreeBlockMaster>
+    /// Upper bound for the total size of all regions
+    max_capacity: usize,
 
+    /// Backend of new regions
+    backend: ArenaBackend,
+
+    /// Free block tracking of new regions
+    strategy: FreeBlockStrategy,
+
+    /// Blocks that compaction may move, by address
+    relocatable: BTreeMap<usize, RelocatableBlock>,
 }
 
+impl ArenaState {
+    fn capacity(&self) -> usize {
+        self.regions.iter().map(|region| region.capacity).sum()
+    }
+
+    /// Allocates from the existing regions, adding a new region if none of them has room
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if let Some(ptr) = self.allocate_existing(layout) {
+            return Some(ptr);
+        }
+
+        let mut region = ArenaRegion::new(self.next_region_capacity(layout)?, self.backend, self.strategy).ok()?;
+        let ptr = region.allocate(layout);
+        self.regions.push(region);
+        ptr
+    }
+
+    /// Allocates from the existing regions only, never growing the arena
+    fn allocate_existing(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.regions.iter_mut().rev().find_map(|region| region.allocate(layout))
+    }
+
+    fn deallocate(&mut self, ptr: NonNull<u8>, size: usize) {
+        let Some((region, start)) = self.find_region(ptr) else {
+            debug_assert!(false, "Pointer does not belong to the arena");
+            return;
+        };
+        let end = start + ArenaRegion::block_size(size);
+        debug_assert!(end <= region.capacity, "Block exceeds its region");
+
+        region.free_blocks.insert(MemFreeBlock::new(start, end));
+        region.release(start, end);
+    }
+
+    /// Resizes a block by taking from or giving back to the free space right after it
+    fn resize(&mut self, ptr: NonNull<u8>, old_size: usize, new_size: usize) -> bool {
+        let Some((region, start)) = self.find_region(ptr) else {
+            return false;
+        };
+        let (old_size, new_size) = (ArenaRegion::block_size(old_size), ArenaRegion::block_size(new_size));
+
+        if new_size > old_size {
+            if !region.free_blocks.take_front(start + old_size, new_size - old_size) {
+                return false;
+            }
+
+            if !region.commit(start + new_size) {
+                region.free_blocks.insert(MemFreeBlock::new(start + old_size, start + new_size));
+                return false;
+            }
+
+            true
+        } else {
+            if new_size < old_size {
+                region.free_blocks.insert(MemFreeBlock::new(start + new_size, start + old_size));
+            }
+            true
+        }
+    }
+
+    /// Compacts every region, returns the old address, new address and block of every move
+    fn compact(&mut self) -> Vec<(usize, NonNull<u8>, RelocatableBlock)> {
+        let mut moves = Vec::new();
+
+        for region in &mut self.regions {
+            let base = region.ptr.as_ptr() as usize;
+            let blocks: Vec<(usize, Layout)> = self.relocatable.range(base..base + region.capacity)
+                .map(|(&address, block)| (address - base, block.layout))
+                .collect();
+
+            if blocks.is_empty() {
+                continue;
+            }
+
+            let targets = region.compact(&blocks, self.strategy);
+            for (&(start, _), target) in blocks.iter().zip(targets) {
+                if target != start {
+                    let new = unsafe { region.ptr.add(target) };
+                    moves.push((base + start, new, self.relocatable[&(base + start)]));
+                }
+            }
+        }
+
+        // A block may move to where another moved block used to be, so clear all old keys first
+        for &(old, _, _) in &moves {
+            self.relocatable.remove(&old);
+        }
+        for &(_, new, block) in &moves {
+            self.relocatable.insert(new.as_ptr() as usize, block);
+        }
+
+        moves
+    }
+
+    /// Region containing `ptr` and the offset of `ptr` inside it
+    fn find_region(&mut self, ptr: NonNull<u8>) -> Option<(&mut ArenaRegion, usize)> {
+        self.regions.iter_mut()
+            .find_map(|region| region.offset_of(ptr).map(|start| (region, start)))
+    }
+
+    /// Size of the region to add for `layout`, `None` if it would exceed the limit
+    ///
+    /// Regions grow geometrically by `GROWTH_FACTOR`, but are always large enough
+    /// for the request itself.
+    fn next_region_capacity(&self, layout: Layout) -> Option<usize> {
+        let last_capacity = self.regions.last().map_or(0, |region| region.capacity);
+        let required = align_up(layout.size() + layout.align() - 1, CACHE_LINE_SIZE);
+        let grown = align_up((last_capacity as f32 * GROWTH_FACTOR) as usize, CACHE_LINE_SIZE);
+
+        // Regions are cache line sized, so round the remaining budget down to whole lines
+        let remaining = self.max_capacity.saturating_sub(self.capacity());
+        let remaining = remaining - remaining % CACHE_LINE_SIZE;
+        if required > remaining {
+            return None;
+        }
+
+        Some(grown.clamp(required, remaining))
+    }
+}
+
+/// Free blocks of the small size classes kept aside for one group of threads
+struct ThreadCache {
+    bins: [Vec<NonNull<u8>>; SIZE_CLASSES],
+}
+
+impl ThreadCache {
+    fn new() -> Self {
+        Self { bins: std::array::from_fn(|_| Vec::new()) }
+    }
+}
+
+// Cached blocks are plain arena memory, not tied to the thread that freed them
+unsafe impl Send for ThreadCache {}
+
+/// Memory arena made of one or more backing regions
+///
+/// Starts with a single region and adds a new one when no region can serve an
+/// allocation. Regions are never moved or released before the arena is dropped,
+/// so pointers handed out earlier stay valid while the arena grows.
+///
+/// The arena can be shared between threads. Small allocations are rounded up to a
+/// power-of-two size class and served from per-thread caches, so threads only touch
+/// the shared free blocks to refill or trim a cache. A block freed on another thread
+/// simply goes to that thread's cache, blocks of one size class are interchangeable.
+///
+/// Next to the free blocks the arena keeps a scratch region for per-frame data:
+/// a bump allocator that is reset all at once with `reset_scratch`.
+pub struct Arena {
+    state: Mutex<ArenaState>,
+
+    /// Per-thread caches, a thread always uses the stripe of its slot number
+    caches: Box<[Mutex<ThreadCache>]>,
+
+    /// Scratch region, taken from the free blocks on the first scratch allocation
+    scratch: OnceLock<ScratchRegion>,
+
+    /// Size of the scratch region in bytes
+    scratch_capacity: usize,
+
+    /// Usage totals reported by `stats`
+    counters: ArenaCounters,
+
+    /// Live allocations for the leak report, only filled in debug builds
+    tracker: AllocationTracker,
+
+    /// Receives the leak report on drop
+    leak_hook: Option<LeakHook>,
+
+    /// Canaries and guard pages around every allocation
+    #[cfg(feature = "debug-arena")]
+    debug: DebugArena,
+}
+
+// Regions are only touched under the state lock, cached blocks under their stripe lock
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
 impl Arena {
+    /// Creates an arena that may grow up to `MAX_EXPANSION_FACTOR` times its initial capacity
     pub fn with_capacity(capacity: usize) -> Self {
-        let aligned_capacity = align_up(capacity, CACHE_LINE_SIZE);
+        Self::with_limit(capacity, capacity.saturating_mul(MAX_EXPANSION_FACTOR))
+    }
 
-        let layout = Layout::from_size_align(aligned_capacity, CACHE_LINE_SIZE)
-            .expect("Invalid layout for arena");
+    /// Creates an arena whose regions never add up to more than `max_capacity` bytes
+    ///
+    /// Passing the same value for both sizes gives a fixed-size arena.
+    pub fn with_limit(capacity: usize, max_capacity: usize) -> Self {
+        Self::with_scratch(capacity, max_capacity, DEFAULT_SCRATCH_SIZE)
+    }
 
-        let ptr = unsafe { alloc(layout) };
-        let ptr = NonNull::new(ptr).expect("Failed to allocate memory for arena");
+    /// Creates an arena with a scratch region of `scratch_capacity` bytes
+    ///
+    /// The scratch region counts against the arena's capacity once it is used.
+    pub fn with_scratch(capacity: usize, max_capacity: usize, scratch_capacity: usize) -> Self {
+        Self::with_config(ArenaConfig { capacity, max_capacity, scratch_capacity, ..ArenaConfig::default() })
+    }
+
+    /// Creates an arena from the config, panics if its first region cannot be allocated
+    pub fn with_config(config: ArenaConfig) -> Self {
+        Self::try_with_config(config)
+            .unwrap_or_else(|error| panic!("Failed to allocate memory for arena: {error}"))
+    }
+
+    /// Creates an arena from the config
+    ///
+    /// The mmap backend reserves `max_capacity` bytes of address space as its only
+    /// region and commits them on demand, so the arena never needs a second region.
+    pub fn try_with_config(config: ArenaConfig) -> EcsResult<Self> {
+        let initial_capacity = match config.backend {
+            ArenaBackend::System => config.capacity,
+            #[cfg(target_os = "linux")]
+            ArenaBackend::Mmap(_) => config.capacity.max(config.max_capacity),
+        };
+
+        let region = ArenaRegion::new(initial_capacity, config.backend, config.free_blocks)?;
+        Ok(Self::from_region(region, config))
+    }
+
+    /// Fixed-size arena over `capacity` bytes at `ptr`, taken from a parent arena
+    ///
+    /// The arena has no scratch region and never grows. The block must stay valid
+    /// until the arena is dropped.
+    pub(crate) unsafe fn in_parent_block(ptr: NonNull<u8>, capacity: usize) -> Self {
+        let config = ArenaConfig {
+            capacity,
+            max_capacity: capacity,
+            scratch_capacity: 0,
+            ..ArenaConfig::default()
+        };
+
+        Self::from_region(ArenaRegion::in_parent_block(ptr, capacity), config)
+    }
+
+    fn from_region(region: ArenaRegion, config: ArenaConfig) -> Self {
+        let max_capacity = config.max_capacity.max(region.capacity);
 
         Self {
-            ptr,
-            capacity: aligned_capacity,
-            cursor: UnsafeCell::new(0),
-            layout,
-            free_blocks: UnsafeCell::new(MemFreeBlockMaster::new_init(capacity)),
+            state: Mutex::new(ArenaState {
+                regions: vec![region],
+                max_capacity,
+                backend: config.backend,
+                strategy: config.free_blocks,
+                relocatable: BTreeMap::new(),
+            }),
+            caches: (0..THREAD_CACHE_STRIPES).map(|_| Mutex::new(ThreadCache::new())).collect(),
+            scratch: OnceLock::new(),
+            scratch_capacity: align_up(config.scratch_capacity, CACHE_LINE_SIZE),
+            counters: ArenaCounters::new(),
+            tracker: AllocationTracker::new(),
+            leak_hook: config.leak_hook,
+            #[cfg(feature = "debug-arena")]
+            debug: DebugArena::new(config.guard_pages),
         }
     }
 
@@ -41,33 +626,613 @@ impl Arena {
         Self::with_capacity(DEFAULT_ARENA_SIZE)
     }
 
-    pub fn allocate_layout(&self, layout: Layout) -> NonNull<u8> {
-        match self.allocate_from_free_blocks(layout) {
-            Some(ptr) => ptr,
-            None => panic!("Arena out of memory: no suitable free blocks available")
+    /// Total size of all backing regions
+    pub fn capacity(&self) -> usize {
+        self.state().capacity()
+    }
+
+    /// Limit the arena can grow to
+    pub fn max_capacity(&self) -> usize {
+        self.state().max_capacity
+    }
+
+    /// Number of bytes backed by memory, below `capacity` for lazily committed regions
+    pub fn committed(&self) -> usize {
+        self.state().regions.iter().map(ArenaRegion::committed).sum()
+    }
+
+    /// Number of backing regions
+    pub fn region_count(&self) -> usize {
+        self.state().regions.len()
+    }
+
+    /// Carves a child arena of `size` bytes out of this one, panics if there is no room
+    #[track_caller]
+    pub fn sub_arena(&self, size: usize) -> SubArena<'_> {
+        self.try_sub_arena(size)
+            .unwrap_or_else(|error| panic!("Failed to allocate sub-arena: {error}"))
+    }
+
+    /// Carves a child arena of `size` bytes out of this one
+    ///
+    /// Everything allocated from the child is released at once when it is dropped,
+    /// by giving its whole block back to this arena.
+    #[track_caller]
+    pub fn try_sub_arena(&self, size: usize) -> EcsResult<SubArena<'_>> {
+        SubArena::new(self, size)
+    }
+
+    /// Forgets all live allocations, their memory goes away with the whole arena
+    pub(crate) fn discard_allocations(&self) {
+        self.tracker.clear();
+    }
+
+    /// Allocates a block that `compact` may move, counted towards `owner` in the stats
+    ///
+    /// Relocatable blocks bypass the thread caches and must be returned with
+    /// `deallocate_relocatable` and the same owner.
+    #[track_caller]
+    pub fn try_allocate_relocatable(&self, layout: Layout, owner: &'static str) -> EcsResult<NonNull<u8>> {
+        if layout.size() == 0 {
+            return Ok(Self::dangling(layout));
         }
+
+        let allocated = Cell::new(None);
+        let allocate = |block_layout: Layout| -> EcsResult<NonNull<u8>> {
+            // The state lock has to be released before the caches are flushed
+            let block = self.state().allocate(block_layout);
+            let block = block
+                .or_else(|| {
+                    self.flush_thread_caches();
+                    self.state().allocate(block_layout)
+                })
+                .ok_or(EcsError::OutOfMemory { size: block_layout.size(), align: block_layout.align() })?;
+
+            allocated.set(Some((block, block_layout)));
+            Ok(block)
+        };
+
+        #[cfg(feature = "debug-arena")]
+        let ptr = self.debug.allocate(layout, allocate)?;
+        #[cfg(not(feature = "debug-arena"))]
+        let ptr = allocate(layout)?;
+
+        // Guarded debug allocations live outside the regions and are never moved
+        if let Some((block, block_layout)) = allocated.get() {
+            let offset = ptr.as_ptr() as usize - block.as_ptr() as usize;
+            self.state().relocatable.insert(block.as_ptr() as usize, RelocatableBlock { layout: block_layout, offset });
+        }
+
+        self.counters.record_allocate(layout.size());
+        self.counters.record_owner_allocate(owner, layout.size());
+        self.track(ptr, layout, Some(owner));
+        Ok(ptr)
+    }
+
+    /// Returns memory obtained from `try_allocate_relocatable`
+    pub fn deallocate_relocatable(&self, ptr: NonNull<u8>, layout: Layout, owner: &'static str) {
+        if layout.size() == 0 {
+            return;
+        }
+
+        let deallocate = |block: NonNull<u8>, block_layout: Layout| {
+            let mut state = self.state();
+            state.relocatable.remove(&(block.as_ptr() as usize));
+            state.deallocate(block, block_layout.size());
+        };
+
+        #[cfg(feature = "debug-arena")]
+        self.debug.deallocate(ptr, layout, deallocate)
+            .unwrap_or_else(|error| self.report_corruption(ptr, error));
+        #[cfg(not(feature = "debug-arena"))]
+        deallocate(ptr, layout);
+
+        self.counters.record_deallocate(layout.size());
+        self.counters.record_owner_deallocate(owner, layout.size());
+        self.tracker.remove(ptr.as_ptr() as usize);
+    }
+
+    /// Marks a live allocation as a chunk of the pool, shown in the leak report
+    pub(crate) fn tag_pool(&self, ptr: NonNull<u8>, pool: PoolTag) {
+        self.tracker.set_pool(ptr.as_ptr() as usize, pool);
+    }
+
+    /// Moves relocatable allocations towards the start of their regions
+    ///
+    /// Thread caches are flushed first, then every region packs its relocatable blocks
+    /// at the front as far as pinned allocations allow, leaving one large free block
+    /// at its end. Allocations that did not move are not part of the result.
+    ///
+    /// # Safety
+    ///
+    /// No relocatable allocation may be accessed while this runs, and their owners must
+    /// switch to the addresses in the returned relocations before using them again.
+    pub unsafe fn compact(&self) -> Relocations {
+        self.flush_thread_caches();
+        let moves = self.state().compact();
+
+        let mut relocations = Relocations::default();
+        for (old_block, new_block, block) in moves {
+            let old = old_block + block.offset;
+            let new = unsafe { new_block.add(block.offset) };
+
+            self.tracker.relocate(old, new.as_ptr() as usize);
+            relocations.moves.insert(old, new);
+        }
+
+        relocations
+    }
+
+    /// Fragmentation of the most fragmented region, from 0 to 1
+    pub fn fragmentation(&self) -> f32 {
+        self.state().regions.iter().map(ArenaRegion::fragmentation).fold(0.0, f32::max)
+    }
+
+    /// True once some region is fragmented beyond `COMPACTION_THRESHOLD`
+    pub fn needs_compaction(&self) -> bool {
+        self.fragmentation() > COMPACTION_THRESHOLD
+    }
+
+    /// Allocations that are still live, with their owner and allocation site
+    ///
+    /// Only debug builds track allocations, release builds always report none.
+    pub fn leak_report(&self) -> LeakReport {
+        self.tracker.report()
     }
 
+    /// Current memory usage, free space and per-owner breakdown
+    pub fn stats(&self) -> ArenaStats {
+        // Caches before the state, the same lock order as everywhere else
+        let cached_bytes = self.caches.iter()
+            .map(|cache| {
+                let cache = cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                cache.bins.iter().enumerate()
+                    .map(|(class, bin)| bin.len() * Self::class_size(class))
+                    .sum::<usize>()
+            })
+            .sum();
+
+        let state = self.state();
+        let free_bytes = state.regions.iter().map(|region| region.free_blocks.total_free_size()).sum();
+        let largest_free_block = state.regions.iter()
+            .map(|region| region.free_blocks.largest_free_block())
+            .max()
+            .unwrap_or(0);
+
+        let mut stats = ArenaStats {
+            capacity: state.capacity(),
+            committed: state.regions.iter().map(ArenaRegion::committed).sum(),
+            used_bytes: 0,
+            free_bytes,
+            cached_bytes,
+            largest_free_block,
+            free_block_count: state.regions.iter().map(|region| region.free_blocks.block_count()).sum(),
+            fragmentation: ArenaStats::fragmentation(free_bytes, largest_free_block),
+            allocation_count: 0,
+            total_allocations: 0,
+            high_water_mark: 0,
+            owners: Vec::new(),
+        };
+        drop(state);
+
+        self.counters.fill(&mut stats);
+        stats
+    }
+
+    /// Allocates memory for the layout, panics if the arena is out of memory
+    ///
+    /// Zero-sized layouts never touch the free blocks, they get a dangling
+    /// pointer with the requested alignment.
+    #[track_caller]
+    pub fn allocate_layout(&self, layout: Layout) -> NonNull<u8> {
+        self.try_allocate_layout(layout)
+            .unwrap_or_else(|error| panic!("Arena allocation failed: {error}"))
+    }
+
+    /// Allocates memory for the layout, reporting `OutOfMemory` instead of panicking
+    #[track_caller]
+    pub fn try_allocate_layout(&self, layout: Layout) -> EcsResult<NonNull<u8>> {
+        self.try_allocate_tagged(layout, None)
+    }
+
+    /// Allocates memory for the layout and counts it towards `owner` in the stats
+    ///
+    /// Memory from here must be returned with `deallocate_owned` and the same owner.
+    #[track_caller]
+    pub fn try_allocate_owned(&self, layout: Layout, owner: &'static str) -> EcsResult<NonNull<u8>> {
+        let ptr = self.try_allocate_tagged(layout, Some(owner))?;
+        self.counters.record_owner_allocate(owner, layout.size());
+        Ok(ptr)
+    }
+
+    /// Returns memory obtained from `try_allocate_owned`
+    pub fn deallocate_owned(&self, ptr: NonNull<u8>, layout: Layout, owner: &'static str) {
+        self.deallocate(ptr, layout);
+        self.counters.record_owner_deallocate(owner, layout.size());
+    }
+
+    /// Allocates directly from the shared free blocks, bypassing the thread caches
+    ///
+    /// Memory from here must be returned with `deallocate_to_free_blocks`.
+    #[track_caller]
     pub fn allocate_from_free_blocks(&self, layout: Layout) -> Option<NonNull<u8>> {
-        let size = layout.size();
-        let align = layout.align();
+        #[cfg(feature = "debug-arena")]
+        let ptr = self.debug.allocate(layout, |padded| {
+            self.state().allocate(padded)
+                .ok_or(EcsError::OutOfMemory { size: padded.size(), align: padded.align() })
+        }).ok()?;
+        #[cfg(not(feature = "debug-arena"))]
+        let ptr = self.state().allocate(layout)?;
+
+        self.counters.record_allocate(layout.size());
+        self.track(ptr, layout, None);
+        Some(ptr)
+    }
 
-        let free_blocks = unsafe { &mut *self.free_blocks.get() };
+    #[track_caller]
+    fn try_allocate_tagged(&self, layout: Layout, owner: Option<&'static str>) -> EcsResult<NonNull<u8>> {
+        if layout.size() == 0 {
+            return Ok(Self::dangling(layout));
+        }
 
-        let block = free_blocks.allocate_aligned(size, align)?;
+        #[cfg(feature = "debug-arena")]
+        let ptr = self.debug.allocate(layout, |padded| self.allocate_block(padded))?;
+        #[cfg(not(feature = "debug-arena"))]
+        let ptr = self.allocate_block(layout)?;
 
-        let ptr = unsafe {
-            self.ptr.as_ptr().add(block.start)
+        self.counters.record_allocate(layout.size());
+        self.track(ptr, layout, owner);
+        Ok(ptr)
+    }
+
+    /// Takes a block from the thread cache or the shared free blocks
+    fn allocate_block(&self, layout: Layout) -> EcsResult<NonNull<u8>> {
+        let allocate = || match Self::size_class(layout) {
+            Some(class) => self.allocate_cached(class),
+            None => self.state().allocate(layout),
         };
 
-        NonNull::new(ptr)
+        // Blocks parked in the thread caches may be what is missing, give them back and retry
+        allocate()
+            .or_else(|| {
+                self.flush_thread_caches();
+                allocate()
+            })
+            .ok_or(EcsError::OutOfMemory { size: layout.size(), align: layout.align() })
+    }
+
+    /// Records a live allocation of the caller for the leak report
+    #[track_caller]
+    #[inline]
+    fn track(&self, ptr: NonNull<u8>, layout: Layout, owner: Option<&'static str>) {
+        self.tracker.record(AllocationRecord {
+            address: ptr.as_ptr() as usize,
+            size: layout.size(),
+            align: layout.align(),
+            owner,
+            pool: None,
+            location: Location::caller(),
+        });
+    }
+
+    /// Returns memory obtained from `allocate_layout` with the same layout
+    ///
+    /// Small blocks go to the calling thread's cache, everything else is merged
+    /// back into the shared free blocks.
+    pub fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+
+        #[cfg(feature = "debug-arena")]
+        self.debug.deallocate(ptr, layout, |block, padded| self.deallocate_block(block, padded))
+            .unwrap_or_else(|error| self.report_corruption(ptr, error));
+        #[cfg(not(feature = "debug-arena"))]
+        self.deallocate_block(ptr, layout);
+
+        self.counters.record_deallocate(layout.size());
+        self.tracker.remove(ptr.as_ptr() as usize);
+    }
+
+    /// Returns a block to the thread cache or the shared free blocks
+    fn deallocate_block(&self, ptr: NonNull<u8>, layout: Layout) {
+        match Self::size_class(layout) {
+            Some(class) => self.deallocate_cached(ptr, class),
+            None => self.state().deallocate(ptr, layout.size()),
+        }
+    }
+
+    /// Returns memory obtained from `allocate_from_free_blocks`, merging it with
+    /// the adjacent free blocks
+    pub fn deallocate_to_free_blocks(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+
+        #[cfg(feature = "debug-arena")]
+        self.debug.deallocate(ptr, layout, |block, padded| self.state().deallocate(block, padded.size()))
+            .unwrap_or_else(|error| self.report_corruption(ptr, error));
+        #[cfg(not(feature = "debug-arena"))]
+        self.state().deallocate(ptr, layout.size());
+
+        self.counters.record_deallocate(layout.size());
+        self.tracker.remove(ptr.as_ptr() as usize);
+    }
+
+    /// Checks the canaries of all live allocations
+    ///
+    /// Only the `debug-arena` feature places canaries, without it there is nothing to check.
+    pub fn validate(&self) -> EcsResult<()> {
+        #[cfg(feature = "debug-arena")]
+        for record in self.tracker.report().allocations {
+            let layout = Layout::from_size_align(record.size, record.align)?;
+            let ptr = NonNull::new(std::ptr::with_exposed_provenance_mut(record.address))
+                .ok_or(EcsError::MemoryCorruption { address: record.address })?;
+            self.debug.check(ptr, layout)?;
+        }
+
+        Ok(())
+    }
+
+    /// Panics with the allocation site of a block whose canaries were overwritten
+    #[cfg(feature = "debug-arena")]
+    fn report_corruption(&self, ptr: NonNull<u8>, error: EcsError) -> ! {
+        match self.tracker.get(ptr.as_ptr() as usize) {
+            Some(record) => panic!("{error}: {record}"),
+            None => panic!("{error}"),
+        }
     }
 
+    /// Resizes a block without moving it, returns false if it has to be moved instead
+    ///
+    /// Small blocks can be resized within their thread cache size class, larger ones
+    /// grow into the free block right after them and give back the tail when shrinking.
+    /// On success the block must be treated as allocated with `new_layout` from now on.
+    pub fn resize_in_place(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> bool {
+        if old_layout.size() == 0 || new_layout.size() == 0 {
+            return old_layout.size() == new_layout.size();
+        }
+
+        // The canaries sit right behind the block, moving it moves them as well
+        if cfg!(feature = "debug-arena") {
+            return false;
+        }
+
+        if !(ptr.as_ptr() as usize).is_multiple_of(new_layout.align()) {
+            return false;
+        }
+
+        let resized = match (Self::size_class(old_layout), Self::size_class(new_layout)) {
+            (Some(old_class), Some(new_class)) => old_class == new_class,
+            (None, None) => self.state().resize(ptr, old_layout.size(), new_layout.size()),
+            _ => false,
+        };
+
+        if resized {
+            self.counters.record_resize(old_layout.size(), new_layout.size());
+            self.tracker.resize(ptr.as_ptr() as usize, new_layout.size());
+        }
+
+        resized
+    }
+
+    /// Returns the blocks held by all thread caches to the shared free blocks
+    ///
+    /// Useful before a large allocation that needs the small blocks coalesced.
+    pub fn flush_thread_caches(&self) {
+        for cache in self.caches.iter() {
+            // Take the cached blocks out first, a cache lock is never acquired under the state lock
+            let bins = std::mem::replace(
+                &mut *cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner()),
+                ThreadCache::new(),
+            ).bins;
+
+            let mut state = self.state();
+            for (class, bin) in bins.into_iter().enumerate() {
+                for ptr in bin {
+                    state.deallocate(ptr, Self::class_size(class));
+                }
+            }
+        }
+    }
+
+    /// Allocates scratch memory that stays valid until the next `reset_scratch`
+    pub fn scratch_alloc_layout(&self, layout: Layout) -> EcsResult<NonNull<u8>> {
+        if layout.size() == 0 {
+            return Ok(Self::dangling(layout));
+        }
+
+        self.scratch_region()?.alloc(layout)
+    }
+
+    /// Moves the value into scratch memory, its destructor never runs
+    pub fn scratch_alloc<T>(&self, value: T) -> EcsResult<NonNull<T>> {
+        let ptr = self.scratch_alloc_layout(Layout::new::<T>())?.cast::<T>();
+        unsafe { ptr.as_ptr().write(value) };
+        Ok(ptr)
+    }
+
+    /// Opens a scope whose scratch allocations are released when it is dropped
+    pub fn scratch_scope(&self) -> EcsResult<ScratchScope<'_>> {
+        Ok(ScratchScope::new(self.scratch_region()?))
+    }
+
+    /// Releases all scratch allocations at once, usually at the end of a frame
+    ///
+    /// Takes `&mut self`, so no scope can be alive. Pointers from `scratch_alloc`
+    /// must not be used afterwards.
+    pub fn reset_scratch(&mut self) {
+        if let Some(scratch) = self.scratch.get_mut() {
+            scratch.reset();
+        }
+    }
+
+    /// Same as `reset_scratch` for an arena that is shared
+    ///
+    /// # Safety
+    ///
+    /// No scratch allocation or scope may be in use.
+    pub unsafe fn reset_scratch_unchecked(&self) {
+        if let Some(scratch) = self.scratch.get() {
+            unsafe { scratch.reset_unchecked() };
+        }
+    }
+
+    /// Number of scratch bytes allocated since the last reset
+    pub fn scratch_used(&self) -> usize {
+        self.scratch.get().map_or(0, ScratchRegion::used)
+    }
+
+    /// Size of the scratch region
+    pub fn scratch_capacity(&self) -> usize {
+        self.scratch_capacity
+    }
+
+    #[track_caller]
     pub fn allocate<T: Sized>(&self) -> NonNull<T> {
         let layout = Layout::new::<T>();
-        let ptr = self.allocate_layout(layout);
-        unsafe {
-            ptr.cast()
+        self.allocate_layout(layout).cast()
+    }
+
+    #[track_caller]
+    pub fn try_allocate<T: Sized>(&self) -> EcsResult<NonNull<T>> {
+        self.try_allocate_layout(Layout::new::<T>()).map(NonNull::cast)
+    }
+
+    /// Takes a block from the thread cache, refilling it from the shared free blocks
+    fn allocate_cached(&self, class: usize) -> Option<NonNull<u8>> {
+        let mut cache = self.thread_cache();
+        if let Some(ptr) = cache.bins[class].pop() {
+            return Some(ptr);
+        }
+
+        // Carve several blocks out of one span, so the shared lock is taken once per refill.
+        // Spans never grow the arena, only the block actually requested may do that.
+        let block_size = Self::class_size(class);
+        let mut state = self.state();
+        let block_align = Self::class_align(class);
+        let span = Layout::from_size_align(block_size * THREAD_CACHE_REFILL_BLOCKS, block_align).ok()
+            .and_then(|span| state.allocate_existing(span));
+
+        let Some(span) = span else {
+            return state.allocate(Layout::from_size_align(block_size, block_align).ok()?);
+        };
+
+        let bin = &mut cache.bins[class];
+        for index in (1..THREAD_CACHE_REFILL_BLOCKS).rev() {
+            bin.push(unsafe { span.add(index * block_size) });
+        }
+
+        Some(span)
+    }
+
+    /// Keeps a freed block in the thread cache, returning it to the shared free blocks
+    /// once the cache holds enough blocks of its class
+    fn deallocate_cached(&self, ptr: NonNull<u8>, class: usize) {
+        let mut cache = self.thread_cache();
+        let bin = &mut cache.bins[class];
+
+        if bin.len() < THREAD_CACHE_MAX_BLOCKS {
+            bin.push(ptr);
+            return;
+        }
+
+        drop(cache);
+        self.state().deallocate(ptr, Self::class_size(class));
+    }
+
+    /// Size class of a layout, `None` if it is too large for the thread caches
+    ///
+    /// Blocks of a class are aligned to their size up to a cache line, layouts with
+    /// a stricter alignment always go to the shared free blocks.
+    #[inline]
+    fn size_class(layout: Layout) -> Option<usize> {
+        if layout.align() > CACHE_LINE_SIZE {
+            return None;
+        }
+
+        let block_size = layout.size().max(layout.align())
+            .max(THREAD_CACHE_MIN_BLOCK_SIZE)
+            .checked_next_power_of_two()?;
+
+        (block_size <= THREAD_CACHE_MAX_BLOCK_SIZE).then(|| {
+            (block_size.trailing_zeros() - THREAD_CACHE_MIN_BLOCK_SIZE.trailing_zeros()) as usize
+        })
+    }
+
+    #[inline]
+    fn class_size(class: usize) -> usize {
+        THREAD_CACHE_MIN_BLOCK_SIZE << class
+    }
+
+    #[inline]
+    fn class_align(class: usize) -> usize {
+        Self::class_size(class).min(CACHE_LINE_SIZE)
+    }
+
+    /// Scratch region of the arena, taken from the free blocks on first use
+    fn scratch_region(&self) -> EcsResult<&ScratchRegion> {
+        if let Some(scratch) = self.scratch.get() {
+            return Ok(scratch);
+        }
+
+        // The region belongs to the arena itself, so it counts as used but is not tracked as a leak
+        let layout = Layout::from_size_align(self.scratch_capacity, CACHE_LINE_SIZE)?;
+        let ptr = self.state().allocate(layout)
+            .ok_or(EcsError::OutOfMemory { size: layout.size(), align: layout.align() })?;
+        self.counters.record_allocate(layout.size());
+
+        // Another thread may have set the region up in the meantime, ours goes back then
+        let mut created = Some(ScratchRegion::new(ptr, self.scratch_capacity));
+        let scratch = self.scratch.get_or_init(|| created.take().expect("Region is only taken once"));
+        if let Some(unused) = created {
+            self.state().deallocate(unused.as_ptr(), layout.size());
+            self.counters.record_deallocate(layout.size());
         }
+
+        Ok(scratch)
+    }
+
+    fn state(&self) -> MutexGuard<'_, ArenaState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Cache stripe of the calling thread
+    fn thread_cache(&self) -> MutexGuard<'_, ThreadCache> {
+        static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+        thread_local! {
+            static THREAD_SLOT: usize = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let slot = THREAD_SLOT.with(|slot| *slot) % self.caches.len();
+        self.caches[slot].lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Well-aligned non-null pointer for zero-sized allocations
+    #[inline]
+    fn dangling(layout: Layout) -> NonNull<u8> {
+        NonNull::new(std::ptr::without_provenance_mut(layout.align()))
+            .expect("Alignment is never zero")
     }
-}
\ No newline at end of file
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        let Some(leak_hook) = self.leak_hook else {
+            return;
+        };
+
+        // Everything still tracked at this point can never be freed anymore
+        let report = self.tracker.report();
+        if !report.is_empty() {
+            leak_hook(&report);
+        }
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,110 @@
+use std::alloc::Layout;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use crate::ecs::error::EcsResult;
+use crate::ecs::memory::arena::Arena;
+
+/// Owned value in arena memory, freed back into the arena on drop
+///
+/// The lifetime ties the box to its arena, so it can never outlive it.
+///
+/// ```compile_fail
+/// use boyko_ecs::ecs::memory::arena::Arena;
+/// use boyko_ecs::ecs::memory::arena_box::ArenaBox;
+///
+/// let boxed = {
+///     let arena = Arena::new();
+///     ArenaBox::new_in(42u32, &arena)
+/// };
+/// ```
+pub struct ArenaBox<'a, T> {
+    ptr: NonNull<T>,
+
+    arena: &'a Arena,
+
+    /// The box owns a `T`
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T> ArenaBox<'a, T> {
+    /// Moves the value into the arena, panics if the arena is out of memory
+    #[track_caller]
+    pub fn new_in(value: T, arena: &'a Arena) -> Self {
+        Self::try_new_in(value, arena)
+            .unwrap_or_else(|error| panic!("Failed to allocate arena box: {error}"))
+    }
+
+    /// Moves the value into the arena
+    #[track_caller]
+    pub fn try_new_in(value: T, arena: &'a Arena) -> EcsResult<Self> {
+        let ptr = arena.try_allocate_layout(Layout::new::<T>())?.cast::<T>();
+        unsafe { ptr.as_ptr().write(value) };
+
+        Ok(Self { ptr, arena, _marker: PhantomData })
+    }
+
+    /// Moves the value out of the arena, freeing its memory
+    pub fn into_inner(self) -> T {
+        let this = ManuallyDrop::new(self);
+
+        unsafe {
+            let value = this.ptr.as_ptr().read();
+            this.arena.deallocate(this.ptr.cast(), Layout::new::<T>());
+            value
+        }
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+
+    #[inline]
+    pub fn arena(&self) -> &'a Arena {
+        self.arena
+    }
+}
+
+impl<T> Deref for ArenaBox<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for ArenaBox<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for ArenaBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            std::ptr::drop_in_place(self.ptr.as_ptr());
+            self.arena.deallocate(self.ptr.cast(), Layout::new::<T>());
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for ArenaBox<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+// The box owns its value like `Box<T>` does, the arena itself is `Sync`
+unsafe impl<T: Send> Send for ArenaBox<'_, T> {}
+unsafe impl<T: Sync> Sync for ArenaBox<'_, T> {}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Snapshot of an arena's memory usage
+///
+/// Used bytes count the sizes that were requested, so the padding of size classes
+/// and alignment shows up as the difference between capacity and the other totals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaStats {
+    /// Total size of all backing regions
+    pub capacity: usize,
+
+    /// Bytes backed by memory, below `capacity` for lazily committed regions
+    pub committed: usize,
+
+    /// Bytes held by live allocations, the scratch region included
+    pub used_bytes: usize,
+
+    /// Bytes in the shared free blocks
+    pub free_bytes: usize,
+
+    /// Bytes parked in the thread caches
+    pub cached_bytes: usize,
+
+    pub largest_free_block: usize,
+
+    pub free_block_count: usize,
+
+    /// Share of the free bytes outside the largest free block, from 0 to 1
+    pub fragmentation: f32,
+
+    /// Number of live allocations
+    pub allocation_count: usize,
+
+    /// Number of allocations since the arena was created
+    pub total_allocations: usize,
+
+    /// Highest value `used_bytes` has reached
+    pub high_water_mark: usize,
+
+    /// Live memory per owner, largest first
+    pub owners: Vec<OwnerStats>,
+}
+
+/// Live memory allocated under one owner tag, e.g. a component type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OwnerStats {
+    pub owner: &'static str,
+
+    pub bytes: usize,
+
+    pub allocations: usize,
+}
+
+impl ArenaStats {
+    /// Fragmentation for a free space of `free_bytes` whose largest block is `largest_free_block`
+    pub(crate) fn fragmentation(free_bytes: usize, largest_free_block: usize) -> f32 {
+        if free_bytes == 0 {
+            return 0.0;
+        }
+
+        1.0 - largest_free_block as f32 / free_bytes as f32
+    }
+}
+
+impl fmt::Display for ArenaStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "capacity:           {} bytes ({} committed)", self.capacity, self.committed)?;
+        writeln!(f, "used:               {} bytes in {} allocations", self.used_bytes, self.allocation_count)?;
+        writeln!(f, "high-water mark:    {} bytes", self.high_water_mark)?;
+        writeln!(f, "free:               {} bytes in {} blocks", self.free_bytes, self.free_block_count)?;
+        writeln!(f, "cached:             {} bytes", self.cached_bytes)?;
+        writeln!(f, "largest free block: {} bytes", self.largest_free_block)?;
+        write!(f, "fragmentation:      {:.1}%", self.fragmentation * 100.0)?;
+
+        for owner in &self.owners {
+            write!(f, "\n  {}: {} bytes in {} allocations", owner.owner, owner.bytes, owner.allocations)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Running totals behind `ArenaStats`
+pub(crate) struct ArenaCounters {
+    used: AtomicUsize,
+
+    allocations: AtomicUsize,
+
+    total_allocations: AtomicUsize,
+
+    high_water_mark: AtomicUsize,
+
+    /// Live bytes and allocations per owner tag
+    owners: Mutex<HashMap<&'static str, (usize, usize)>>,
+}
+
+impl ArenaCounters {
+    pub(crate) fn new() -> Self {
+        Self {
+            used: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
+            total_allocations: AtomicUsize::new(0),
+            high_water_mark: AtomicUsize::new(0),
+            owners: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn record_allocate(&self, size: usize) {
+        let used = self.used.fetch_add(size, Ordering::Relaxed) + size;
+        self.high_water_mark.fetch_max(used, Ordering::Relaxed);
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.total_allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_deallocate(&self, size: usize) {
+        self.used.fetch_sub(size, Ordering::Relaxed);
+        self.allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_resize(&self, old_size: usize, new_size: usize) {
+        if new_size > old_size {
+            let used = self.used.fetch_add(new_size - old_size, Ordering::Relaxed) + new_size - old_size;
+            self.high_water_mark.fetch_max(used, Ordering::Relaxed);
+        } else {
+            self.used.fetch_sub(old_size - new_size, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_owner_allocate(&self, owner: &'static str, size: usize) {
+        let mut owners = self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (bytes, allocations) = owners.entry(owner).or_default();
+        *bytes += size;
+        *allocations += 1;
+    }
+
+    pub(crate) fn record_owner_deallocate(&self, owner: &'static str, size: usize) {
+        let mut owners = self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((bytes, allocations)) = owners.get_mut(owner) {
+            *bytes -= size;
+            *allocations -= 1;
+
+            if *allocations == 0 {
+                owners.remove(owner);
+            }
+        }
+    }
+
+    /// Fills in the counter-based fields of a snapshot
+    pub(crate) fn fill(&self, stats: &mut ArenaStats) {
+        stats.used_bytes = self.used.load(Ordering::Relaxed);
+        stats.allocation_count = self.allocations.load(Ordering::Relaxed);
+        stats.total_allocations = self.total_allocations.load(Ordering::Relaxed);
+        stats.high_water_mark = self.high_water_mark.load(Ordering::Relaxed);
+
+        let owners = self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        stats.owners = owners.iter()
+            .map(|(&owner, &(bytes, allocations))| OwnerStats { owner, bytes, allocations })
+            .collect();
+        stats.owners.sort_unstable_by(|a, b| b.bytes.cmp(&a.bytes).then(a.owner.cmp(b.owner)));
+    }
+}
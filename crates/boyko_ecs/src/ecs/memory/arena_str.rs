@@ -0,0 +1,130 @@
+use std::alloc::Layout;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+use crate::ecs::error::EcsResult;
+use crate::ecs::memory::arena::Arena;
+
+/// Interned string stored in arena memory
+///
+/// Every distinct string is stored once per interner, so two `ArenaStr` from the
+/// same interner are equal exactly when they point to the same bytes, and comparing
+/// them is a pointer comparison. Copying an `ArenaStr` is free.
+#[derive(Clone, Copy)]
+pub struct ArenaStr<'i> {
+    value: &'i str,
+}
+
+impl<'i> ArenaStr<'i> {
+    #[inline]
+    pub fn as_str(&self) -> &'i str {
+        self.value
+    }
+}
+
+impl Deref for ArenaStr<'_> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.value
+    }
+}
+
+impl PartialEq for ArenaStr<'_> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.value, other.value)
+    }
+}
+
+impl Eq for ArenaStr<'_> {}
+
+impl Hash for ArenaStr<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.as_ptr().hash(state);
+    }
+}
+
+impl fmt::Debug for ArenaStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.value, f)
+    }
+}
+
+impl fmt::Display for ArenaStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.value, f)
+    }
+}
+
+/// Deduplicating string storage in an arena
+///
+/// Strings live until the interner is dropped, which frees them back into the arena.
+/// Interned strings borrow the interner, so they cannot outlive it.
+pub struct StrInterner<'a> {
+    arena: &'a Arena,
+
+    /// Interned strings, pointing into arena memory owned by the interner
+    strings: Mutex<HashSet<&'a str>>,
+}
+
+impl<'a> StrInterner<'a> {
+    pub fn new(arena: &'a Arena) -> Self {
+        Self { arena, strings: Mutex::new(HashSet::new()) }
+    }
+
+    /// Returns the interned copy of the string, panics if the arena is out of memory
+    #[track_caller]
+    pub fn intern(&self, value: &str) -> ArenaStr<'_> {
+        self.try_intern(value)
+            .unwrap_or_else(|error| panic!("Failed to intern string: {error}"))
+    }
+
+    /// Returns the interned copy of the string, copying it into the arena on first use
+    #[track_caller]
+    pub fn try_intern(&self, value: &str) -> EcsResult<ArenaStr<'_>> {
+        let mut strings = self.strings.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(&interned) = strings.get(value) {
+            return Ok(ArenaStr { value: interned });
+        }
+
+        let ptr = self.arena.try_allocate_layout(Layout::for_value(value))?;
+        let interned = unsafe {
+            ptr.as_ptr().copy_from_nonoverlapping(value.as_ptr(), value.len());
+            std::str::from_utf8_unchecked(std::slice::from_raw_parts(ptr.as_ptr(), value.len()))
+        };
+
+        strings.insert(interned);
+        Ok(ArenaStr { value: interned })
+    }
+
+    /// Looks the string up without interning it
+    pub fn get(&self, value: &str) -> Option<ArenaStr<'_>> {
+        let strings = self.strings.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        strings.get(value).map(|&interned| ArenaStr { value: interned })
+    }
+
+    /// Number of distinct interned strings
+    pub fn len(&self) -> usize {
+        self.strings.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for StrInterner<'_> {
+    fn drop(&mut self) {
+        let strings = self.strings.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        for value in strings.drain() {
+            let ptr = NonNull::from(value.as_bytes()).cast::<u8>();
+            self.arena.deallocate(ptr, Layout::for_value(value));
+        }
+    }
+}
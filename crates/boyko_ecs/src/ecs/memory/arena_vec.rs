@@ -0,0 +1,268 @@
+use std::alloc::Layout;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use crate::ecs::constants::GROWTH_FACTOR;
+use crate::ecs::error::{EcsError, EcsResult};
+use crate::ecs::memory::arena::Arena;
+
+/// Smallest non-zero capacity of a growing vector
+const MIN_NON_ZERO_CAPACITY: usize = 4;
+
+/// Growable array in arena memory, freed back into the arena on drop
+///
+/// Grows by `GROWTH_FACTOR` and extends its block in place whenever the arena
+/// has free space right after it. The lifetime ties the vector to its arena.
+pub struct ArenaVec<'a, T> {
+    ptr: NonNull<T>,
+
+    len: usize,
+
+    capacity: usize,
+
+    arena: &'a Arena,
+
+    /// The vector owns its elements
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T> ArenaVec<'a, T> {
+    /// Creates an empty vector, nothing is allocated until the first push
+    pub fn new_in(arena: &'a Arena) -> Self {
+        // Zero-sized elements never need memory
+        let capacity = if size_of::<T>() == 0 { usize::MAX } else { 0 };
+
+        Self { ptr: NonNull::dangling(), len: 0, capacity, arena, _marker: PhantomData }
+    }
+
+    /// Creates a vector with room for `capacity` elements, panics if the arena is out of memory
+    #[track_caller]
+    pub fn with_capacity_in(capacity: usize, arena: &'a Arena) -> Self {
+        Self::try_with_capacity_in(capacity, arena)
+            .unwrap_or_else(|error| panic!("Failed to allocate arena vector: {error}"))
+    }
+
+    #[track_caller]
+    pub fn try_with_capacity_in(capacity: usize, arena: &'a Arena) -> EcsResult<Self> {
+        let mut vec = Self::new_in(arena);
+        vec.try_reserve(capacity)?;
+        Ok(vec)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    pub fn arena(&self) -> &'a Arena {
+        self.arena
+    }
+
+    /// Appends an element, panics if the arena is out of memory
+    #[track_caller]
+    pub fn push(&mut self, value: T) {
+        if let Err(error) = self.try_push(value) {
+            panic!("Failed to grow arena vector: {error}");
+        }
+    }
+
+    /// Appends an element, the value is dropped if the vector cannot grow
+    #[track_caller]
+    pub fn try_push(&mut self, value: T) -> EcsResult<()> {
+        if self.len == self.capacity {
+            self.try_reserve(1)?;
+        }
+
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        Some(unsafe { self.ptr.as_ptr().add(self.len).read() })
+    }
+
+    /// Removes an element, moving the last one into its place
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "Index {index} out of bounds for length {}", self.len);
+
+        let last = self.pop().expect("Vector is not empty");
+        if index == self.len {
+            return last;
+        }
+
+        std::mem::replace(&mut self[index], last)
+    }
+
+    /// Drops the elements past `len`
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+
+        let tail = std::ptr::slice_from_raw_parts_mut(unsafe { self.ptr.as_ptr().add(len) }, self.len - len);
+        self.len = len;
+        unsafe { std::ptr::drop_in_place(tail) };
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Reserves room for at least `additional` more elements, panics if the arena is out of memory
+    #[track_caller]
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(error) = self.try_reserve(additional) {
+            panic!("Failed to grow arena vector: {error}");
+        }
+    }
+
+    /// Reserves room for at least `additional` more elements
+    #[track_caller]
+    pub fn try_reserve(&mut self, additional: usize) -> EcsResult<()> {
+        let required = self.len.checked_add(additional)
+            .ok_or(EcsError::CapacityExceeded { capacity: self.capacity })?;
+        if required <= self.capacity {
+            return Ok(());
+        }
+
+        let grown = (self.capacity as f32 * GROWTH_FACTOR) as usize;
+        self.reallocate(required.max(grown).max(MIN_NON_ZERO_CAPACITY))
+    }
+
+    /// Gives unused capacity back to the arena
+    pub fn shrink_to_fit(&mut self) {
+        if size_of::<T>() != 0 && self.capacity > self.len {
+            // Shrinking either happens in place or needs a smaller block, which may fail harmlessly
+            let _ = self.reallocate(self.len);
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Moves the elements into a block for `capacity` elements, resizing in place if possible
+    #[track_caller]
+    fn reallocate(&mut self, capacity: usize) -> EcsResult<()> {
+        let new_layout = Layout::array::<T>(capacity)?;
+        let old_layout = Layout::array::<T>(self.capacity)?;
+
+        if self.capacity != 0 && self.arena.resize_in_place(self.ptr.cast(), old_layout, new_layout) {
+            self.capacity = capacity;
+            return Ok(());
+        }
+
+        let ptr = self.arena.try_allocate_layout(new_layout)?.cast::<T>();
+        unsafe {
+            ptr.as_ptr().copy_from_nonoverlapping(self.ptr.as_ptr(), self.len);
+            self.arena.deallocate(self.ptr.cast(), old_layout);
+        }
+
+        self.ptr = ptr;
+        self.capacity = capacity;
+        Ok(())
+    }
+}
+
+impl<T: Clone> ArenaVec<'_, T> {
+    #[track_caller]
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        self.reserve(values.len());
+        for value in values {
+            self.push(value.clone());
+        }
+    }
+}
+
+impl<T> Deref for ArenaVec<'_, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T> DerefMut for ArenaVec<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T> Extend<T> for ArenaVec<'_, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'v, T> IntoIterator for &'v ArenaVec<'_, T> {
+    type Item = &'v T;
+    type IntoIter = std::slice::Iter<'v, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'v, T> IntoIterator for &'v mut ArenaVec<'_, T> {
+    type Item = &'v mut T;
+    type IntoIter = std::slice::IterMut<'v, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T> Drop for ArenaVec<'_, T> {
+    fn drop(&mut self) {
+        self.clear();
+
+        if let Ok(layout) = Layout::array::<T>(self.capacity) {
+            self.arena.deallocate(self.ptr.cast(), layout);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaVec<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for ArenaVec<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+// The vector owns its elements like `Vec<T>` does, the arena itself is `Sync`
+unsafe impl<T: Send> Send for ArenaVec<'_, T> {}
+unsafe impl<T: Sync> Sync for ArenaVec<'_, T> {}
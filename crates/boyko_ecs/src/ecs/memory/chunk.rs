@@ -1,92 +1,123 @@
 use std::alloc::Layout;
-use std::mem;
+use std::mem::MaybeUninit;
 use std::ptr::NonNull;
+use std::sync::Arc;
 use crate::ecs::core::component::Component;
-use crate::ecs::memory::arena::Arena;
+use crate::ecs::memory::allocation_tracker::PoolTag;
+use crate::ecs::memory::arena::Relocations;
+use crate::ecs::memory::chunk_allocator::ChunkAllocator;
 use crate::ecs::constants::{DEFAULT_COMPONENTS_PER_CHUNK};
+use crate::ecs::error::{EcsError, EcsResult};
 
 /// Chunk хранит фиксированное количество компонентов одного типа
+///
+/// Инварианты:
+/// - `data` указывает на `capacity` слотов, выделенных из `allocator` с раскладкой `layout`
+/// - слоты `0..count` инициализированы, слоты `count..capacity` нет, пропусков не бывает
+/// - `count <= capacity`
+///
+/// Все операции сначала приводят чанк в согласованное состояние и только потом
+/// вызывают деструкторы, поэтому паника в `Drop` компонента может привести
+/// к утечке, но никогда к двойному освобождению или чтению неинициализированной памяти.
 pub struct Chunk<T: Component> {
     /// Указатель на выделенную память
-    data: NonNull<T>,
+    data: NonNull<MaybeUninit<T>>,
+
+    /// Аллокатор, из которого выделена память; чанк держит его живым до своего уничтожения
+    allocator: Arc<dyn ChunkAllocator>,
+
+    /// Раскладка выделенной памяти, нужна для возврата ее аллокатору
+    layout: Layout,
 
     /// Вместимость чанка (максимальное количество компонентов)
     capacity: usize,
 
-    /// Текущее количество занятых слотов
+    /// Количество инициализированных слотов в начале чанка
     count: usize,
 }
 
 impl<T: Component> Chunk<T> {
     /// Создает новый чанк с указанной вместимостью
-    pub fn new(arena: &Arena, capacity: usize) -> Self {
-        // Выделяем память для массива компонентов
-        // Мы должны использовать allocate_layout, так как нам нужен массив
-        let layout = Layout::array::<T>(capacity).expect("Invalid array layout");
-        let ptr = arena.allocate_layout(layout);
+    #[track_caller]
+    pub fn new(allocator: &Arc<dyn ChunkAllocator>, capacity: usize) -> Self {
+        Self::try_new(allocator, capacity)
+            .unwrap_or_else(|error| panic!("Failed to allocate chunk: {error}"))
+    }
 
-        // Приводим указатель к нужному типу
-        let typed_ptr = unsafe { ptr.cast::<T>() };
+    /// Создает новый чанк, возвращая ошибку вместо паники, если памяти не хватило
+    #[track_caller]
+    pub fn try_new(allocator: &Arc<dyn ChunkAllocator>, capacity: usize) -> EcsResult<Self> {
+        Self::try_new_in_pool(allocator, capacity, PoolTag::Standalone)
+    }
 
-        Self {
-            data: typed_ptr,
+    /// Создает чанк хранилища `pool`, аллокатор сообщает его в отчете об утечках
+    #[track_caller]
+    pub fn try_new_in_pool(allocator: &Arc<dyn ChunkAllocator>, capacity: usize, pool: PoolTag) -> EcsResult<Self> {
+        // Выделяем память для массива компонентов
+        // Память учитывается в статистике аллокатора под именем типа компонента,
+        // арена при уплотнении может перенести ее, см. `relocate`
+        let layout = Layout::array::<T>(capacity)?;
+        let ptr = allocator.allocate_chunk(layout, T::name(), pool)?;
+
+        Ok(Self {
+            data: ptr.cast::<MaybeUninit<T>>(),
+            allocator: Arc::clone(allocator),
+            layout,
             capacity,
             count: 0,
-        }
+        })
     }
 
     /// Создает чанк с размером по умолчанию
-    pub fn with_default_capacity(arena: &Arena) -> Self {
-        Self::new(arena, DEFAULT_COMPONENTS_PER_CHUNK)
+    pub fn with_default_capacity(allocator: &Arc<dyn ChunkAllocator>) -> Self {
+        Self::new(allocator, DEFAULT_COMPONENTS_PER_CHUNK)
     }
 
     /// Добавляет компонент в чанк и возвращает его индекс
     pub fn add(&mut self, component: T) -> Option<usize> {
+        self.try_add(component).ok()
+    }
+
+    /// Добавляет компонент в чанк, возвращая `CapacityExceeded`, если чанк заполнен
+    pub fn try_add(&mut self, component: T) -> EcsResult<usize> {
         // Проверяем, что есть место
         if self.count >= self.capacity {
-            return None;
+            return Err(EcsError::CapacityExceeded { capacity: self.capacity });
         }
 
-        // Вычисляем адрес, куда поместить компонент
+        // Первый свободный слот сразу за инициализированными
         let index = self.count;
-        let ptr = unsafe { self.data.as_ptr().add(index) };
-
-        // Размещаем компонент в памяти
-        unsafe {
-            std::ptr::write(ptr, component);
-        }
-
-        // Увеличиваем счетчик
+        self.slot_mut(index).write(component);
         self.count += 1;
 
-        Some(index)
+        Ok(index)
     }
 
+    /// Записывает компонент по индексу, см. `try_set`
     pub fn set(&mut self, index: usize, component: T) -> bool {
+        self.try_set(index, component).is_ok()
+    }
+
+    /// Записывает компонент по индексу
+    ///
+    /// Существующий компонент заменяется и уничтожается, индекс `count` добавляет
+    /// компонент в конец. Запись дальше конца оставила бы неинициализированный пропуск,
+    /// поэтому для нее возвращается `MissingComponent`, а за пределами чанка `CapacityExceeded`.
+    pub fn try_set(&mut self, index: usize, component: T) -> EcsResult<()> {
         if index >= self.capacity {
-            return false;
+            return Err(EcsError::CapacityExceeded { capacity: self.capacity });
         }
 
-        // Если индекс больше текущего количества,
-        // мы автоматически увеличиваем счетчик
-        if index >= self.count {
-            self.count = index + 1;
+        if index == self.count {
+            self.try_add(component)?;
+            return Ok(());
         }
 
-        // Указатель на место в памяти
-        let ptr = unsafe { self.data.as_ptr().add(index) };
-
-        // Размещаем компонент в памяти
-        unsafe {
-            // Если значение уже существует, вызываем его деструктор
-            if index < self.count {
-                std::ptr::drop_in_place(ptr);
-            }
-
-            std::ptr::write(ptr, component);
-        }
+        let slot = self.get_mut(index).ok_or(EcsError::MissingComponent(T::name()))?;
 
-        true
+        // Старое значение уничтожается уже после записи нового
+        drop(std::mem::replace(slot, component));
+        Ok(())
     }
 
     /// Получает ссылку на компонент по индексу
@@ -95,13 +126,8 @@ impl<T: Component> Chunk<T> {
             return None;
         }
 
-        // Получаем указатель на компонент
-        let ptr = unsafe { self.data.as_ptr().add(index) };
-
-        // Преобразуем в ссылку
-        unsafe {
-            Some(&*ptr)
-        }
+        // Слоты до `count` инициализированы
+        Some(unsafe { self.slot(index).assume_init_ref() })
     }
 
     /// Получает изменяемую ссылку на компонент по индексу
@@ -110,13 +136,20 @@ impl<T: Component> Chunk<T> {
             return None;
         }
 
-        // Получаем указатель на компонент
-        let ptr = unsafe { self.data.as_ptr().add(index) };
+        // Слоты до `count` инициализированы
+        Some(unsafe { self.slot_mut(index).assume_init_mut() })
+    }
 
-        // Преобразуем в изменяемую ссылку
-        unsafe {
-            Some(&mut *ptr)
+    /// Получает сырой указатель на компонент по индексу
+    ///
+    /// Указатель ведет в память чанка, поэтому через него можно писать,
+    /// пока никто не держит ссылку на этот же компонент
+    pub fn get_ptr(&self, index: usize) -> Option<NonNull<T>> {
+        if index >= self.count {
+            return None;
         }
+
+        Some(unsafe { self.data.add(index) }.cast::<T>())
     }
 
     /// Возвращает количество компонентов в чанке
@@ -130,108 +163,147 @@ impl<T: Component> Chunk<T> {
     }
 
     /// Возвращает указатель на массив компонентов
+    ///
+    /// Инициализированы только первые `count` элементов
     pub fn as_ptr(&self) -> *const T {
-        self.data.as_ptr()
+        self.data.as_ptr().cast::<T>()
     }
 
     /// Возвращает изменяемый указатель на массив компонентов
+    ///
+    /// Инициализированы только первые `count` элементов
     pub fn as_mut_ptr(&mut self) -> *mut T {
-        self.data.as_ptr()
+        self.data.as_ptr().cast::<T>()
     }
 
     /// Получает срез всех компонентов
     pub fn as_slice(&self) -> &[T] {
+        // Слоты `0..count` инициализированы и идут подряд
         unsafe {
-            std::slice::from_raw_parts(self.data.as_ptr(), self.count)
+            std::slice::from_raw_parts(self.as_ptr(), self.count)
         }
     }
 
     /// Получает изменяемый срез всех компонентов
     pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // Слоты `0..count` инициализированы и идут подряд
         unsafe {
-            std::slice::from_raw_parts_mut(self.data.as_ptr(), self.count)
+            std::slice::from_raw_parts_mut(self.as_mut_ptr(), self.count)
+        }
+    }
+
+    /// Переключает чанк на новый адрес, если арена перенесла его память при уплотнении
+    pub fn relocate(&mut self, relocations: &Relocations) {
+        if let Some(new) = relocations.new_address(self.data.cast()) {
+            self.data = new.cast();
         }
     }
 
     /// Очищает чанк, вызывая деструкторы всех компонентов
     pub fn clear(&mut self) {
-        // Вызываем деструкторы для всех компонентов
-        for i in 0..self.count {
-            let ptr = unsafe { self.data.as_ptr().add(i) };
-            unsafe {
-                std::ptr::drop_in_place(ptr);
-            }
-        }
+        // Сначала помечаем слоты пустыми: если деструктор запаникует,
+        // оставшиеся компоненты утекут, но повторно уничтожены не будут
+        let count = std::mem::replace(&mut self.count, 0);
 
-        // Сбрасываем счетчик
-        self.count = 0;
+        // Срез уничтожает остальные элементы, даже если один из деструкторов паникует
+        unsafe {
+            std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(self.as_mut_ptr(), count));
+        }
     }
 
-    /// Сдвигает элементы, чтобы заполнить пробел при удалении
+    /// Удаляет компонент, сдвигая последующие элементы на его место
     pub fn remove(&mut self, index: usize) -> bool {
         if index >= self.count {
             return false;
         }
 
-        // Получаем указатель на удаляемый компонент
-        let ptr = unsafe { self.data.as_ptr().add(index) };
-
-        // Вызываем деструктор
-        unsafe {
-            std::ptr::drop_in_place(ptr);
-        }
+        // Забираем значение, слот становится неинициализированным
+        let component = unsafe { self.slot(index).assume_init_read() };
 
         // Сдвигаем все последующие элементы на одну позицию назад
         let elements_to_move = self.count - index - 1;
-        if elements_to_move > 0 {
-            unsafe {
-                let src = self.data.as_ptr().add(index + 1);
-                let dst = self.data.as_ptr().add(index);
-                std::ptr::copy(src, dst, elements_to_move);
-            }
+        unsafe {
+            let src = self.data.as_ptr().add(index + 1);
+            let dst = self.data.as_ptr().add(index);
+            std::ptr::copy(src, dst, elements_to_move);
         }
 
-        // Уменьшаем счетчик
         self.count -= 1;
 
+        // Чанк уже согласован, паника в деструкторе ему не навредит
+        drop(component);
         true
     }
 
     /// Удаляет компонент, заменяя его последним (быстрее, но нарушает порядок)
     pub fn swap_remove(&mut self, index: usize) -> bool {
-        if index >= self.count {
-            return false;
-        }
+        self.try_swap_remove(index).is_ok()
+    }
 
-        // Получаем указатель на удаляемый компонент
-        let ptr = unsafe { self.data.as_ptr().add(index) };
+    /// Удаляет компонент, возвращая `MissingComponent`, если слот пуст
+    pub fn try_swap_remove(&mut self, index: usize) -> EcsResult<()> {
+        let component = self.swap_take(index).ok_or(EcsError::MissingComponent(T::name()))?;
 
-        // Вызываем деструктор
-        unsafe {
-            std::ptr::drop_in_place(ptr);
+        // Деструктор вызывается, когда чанк уже согласован
+        drop(component);
+        Ok(())
+    }
+
+    /// Извлекает компонент, заменяя его последним (деструктор не вызывается)
+    pub fn swap_take(&mut self, index: usize) -> Option<T> {
+        if index >= self.count {
+            return None;
         }
 
-        // Если это не последний элемент, заменяем его последним
-        if index < self.count - 1 {
-            let last_index = self.count - 1;
-            let last_ptr = unsafe { self.data.as_ptr().add(last_index) };
+        let last_index = self.count - 1;
 
-            // Перемещаем последний элемент на место удаленного
+        // Забираем значение из чанка, слот становится неинициализированным
+        let component = unsafe { self.slot(index).assume_init_read() };
+
+        // Если это не последний элемент, переносим последний на освободившееся место.
+        // Это обычное перемещение значения: исходный слот сразу перестает считаться живым
+        if index < last_index {
             unsafe {
-                std::ptr::copy(last_ptr, ptr, 1);
+                let last = self.slot(last_index).assume_init_read();
+                self.slot_mut(index).write(last);
             }
         }
 
-        // Уменьшаем счетчик
         self.count -= 1;
 
-        true
+        Some(component)
+    }
+
+    /// Слот по индексу, инициализированный или нет
+    #[inline]
+    fn slot(&self, index: usize) -> &MaybeUninit<T> {
+        debug_assert!(index < self.capacity);
+        unsafe { self.data.add(index).as_ref() }
+    }
+
+    #[inline]
+    fn slot_mut(&mut self, index: usize) -> &mut MaybeUninit<T> {
+        debug_assert!(index < self.capacity);
+        unsafe { self.data.add(index).as_mut() }
     }
 }
 
-// Реализуем Drop, чтобы вызвать деструкторы компонентов
+// Реализуем Drop, чтобы вызвать деструкторы компонентов и вернуть память аллокатору
 impl<T: Component> Drop for Chunk<T> {
     fn drop(&mut self) {
-        self.clear();
+        // Память возвращается и тогда, когда деструктор компонента паникует
+        struct Deallocate<'a, T: Component>(&'a mut Chunk<T>);
+
+        impl<T: Component> Drop for Deallocate<'_, T> {
+            fn drop(&mut self) {
+                let chunk = &mut *self.0;
+                unsafe {
+                    chunk.allocator.deallocate_chunk(chunk.data.cast(), chunk.layout, T::name());
+                }
+            }
+        }
+
+        let guard = Deallocate(self);
+        guard.0.clear();
     }
-}
\ No newline at end of file
+}
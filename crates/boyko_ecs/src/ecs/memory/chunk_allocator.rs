@@ -0,0 +1,141 @@
+use std::alloc::{alloc, dealloc, Layout};
+use std::ptr::NonNull;
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+use crate::ecs::error::{EcsError, EcsResult};
+use crate::ecs::memory::allocation_tracker::PoolTag;
+use crate::ecs::memory::arena::Arena;
+#[cfg(target_os = "linux")]
+use crate::ecs::memory::mmap::{HugePages, MmapRegion};
+
+/// Source of the memory behind component chunks
+///
+/// Chunks and storages hold their allocator as `Arc<dyn ChunkAllocator>`, so it
+/// lives as long as the last chunk allocated from it. `owner` is the name of the
+/// component type, allocators that keep statistics count the chunk towards it.
+/// `pool` is the storage the chunk belongs to, allocators that track allocations
+/// report it together with the caller's location.
+pub trait ChunkAllocator: Send + Sync {
+    #[track_caller]
+    fn allocate_chunk(&self, layout: Layout, owner: &'static str, pool: PoolTag) -> EcsResult<NonNull<u8>>;
+
+    /// Returns a chunk obtained from `allocate_chunk` with the same layout and owner
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from this allocator and must not be used afterwards.
+    unsafe fn deallocate_chunk(&self, ptr: NonNull<u8>, layout: Layout, owner: &'static str);
+}
+
+/// Chunks live in arena memory that `Arena::compact` may move
+impl ChunkAllocator for Arena {
+    #[track_caller]
+    fn allocate_chunk(&self, layout: Layout, owner: &'static str, pool: PoolTag) -> EcsResult<NonNull<u8>> {
+        let ptr = self.try_allocate_relocatable(layout, owner)?;
+        self.tag_pool(ptr, pool);
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate_chunk(&self, ptr: NonNull<u8>, layout: Layout, owner: &'static str) {
+        self.deallocate_relocatable(ptr, layout, owner);
+    }
+}
+
+/// Chunks allocated one by one with `std::alloc::alloc`
+///
+/// Needs no setup, which makes it handy for large, rarely touched components
+/// and for storages created outside of a world.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemChunkAllocator;
+
+impl ChunkAllocator for SystemChunkAllocator {
+    fn allocate_chunk(&self, layout: Layout, _owner: &'static str, _pool: PoolTag) -> EcsResult<NonNull<u8>> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+
+        NonNull::new(unsafe { alloc(layout) })
+            .ok_or(EcsError::OutOfMemory { size: layout.size(), align: layout.align() })
+    }
+
+    unsafe fn deallocate_chunk(&self, ptr: NonNull<u8>, layout: Layout, _owner: &'static str) {
+        if layout.size() != 0 {
+            unsafe { dealloc(ptr.as_ptr(), layout) };
+        }
+    }
+}
+
+/// Every chunk gets an mmap region of its own, unmapped when the chunk is freed
+///
+/// Page granular, so it suits chunks of at least a few pages, optionally on huge pages.
+#[cfg(target_os = "linux")]
+pub struct MmapChunkAllocator {
+    huge_pages: HugePages,
+
+    /// Live regions by address
+    regions: Mutex<HashMap<usize, MmapRegion>>,
+}
+
+#[cfg(target_os = "linux")]
+impl MmapChunkAllocator {
+    pub fn new(huge_pages: HugePages) -> Self {
+        Self { huge_pages, regions: Mutex::new(HashMap::new()) }
+    }
+
+    /// Number of chunks currently mapped
+    pub fn chunk_count(&self) -> usize {
+        self.regions().len()
+    }
+
+    fn regions(&self) -> std::sync::MutexGuard<'_, HashMap<usize, MmapRegion>> {
+        self.regions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Default for MmapChunkAllocator {
+    fn default() -> Self {
+        Self::new(HugePages::Off)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ChunkAllocator for MmapChunkAllocator {
+    fn allocate_chunk(&self, layout: Layout, _owner: &'static str, _pool: PoolTag) -> EcsResult<NonNull<u8>> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+
+        // Regions start at a page boundary, that is all the alignment they can offer
+        if layout.align() > MmapRegion::system_page_size() {
+            return Err(EcsError::InvalidLayout);
+        }
+
+        let mut region = MmapRegion::reserve(layout.size(), self.huge_pages)?;
+        if !region.commit_to(layout.size()) {
+            return Err(EcsError::OutOfMemory { size: layout.size(), align: layout.align() });
+        }
+
+        let ptr = region.as_ptr();
+        self.regions().insert(ptr.as_ptr() as usize, region);
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate_chunk(&self, ptr: NonNull<u8>, layout: Layout, _owner: &'static str) {
+        if layout.size() == 0 {
+            return;
+        }
+
+        let region = self.regions().remove(&(ptr.as_ptr() as usize));
+        debug_assert!(region.is_some(), "Chunk does not belong to the allocator");
+    }
+}
+
+/// Well-aligned non-null pointer for zero-sized chunks
+#[inline]
+fn dangling(layout: Layout) -> NonNull<u8> {
+    NonNull::new(std::ptr::without_provenance_mut(layout.align()))
+        .expect("Alignment is never zero")
+}
@@ -1,13 +1,21 @@
-use std::any::TypeId;
+use std::any::{Any, TypeId};
 use std::marker::PhantomData;
 use std::mem::size_of;
 use std::ptr::NonNull;
-use crate::ecs::core::component::Component;
-use crate::ecs::memory::arena::Arena;
+use std::sync::Arc;
+use crate::ecs::core::component::{Component, ComponentId};
+use crate::ecs::memory::allocation_tracker::PoolTag;
+use crate::ecs::memory::arena::Relocations;
 use crate::ecs::memory::chunk::Chunk;
+use crate::ecs::memory::chunk_allocator::ChunkAllocator;
 use crate::ecs::memory::component_index::UnitId;
+use crate::ecs::error::{EcsError, EcsResult};
 use crate::ecs::constants::{
+    COMPACTION_THRESHOLD,
     DEFAULT_CHUNKS_PER_POOL,
+    GROWTH_FACTOR,
+    MAX_EMPTY_CHUNKS_RATIO,
+    MIN_COMPONENTS_FOR_COMPACTION,
     TINY_COMPONENTS_PER_CHUNK,
     SMALL_COMPONENTS_PER_CHUNK,
     MEDIUM_COMPONENTS_PER_CHUNK,
@@ -17,21 +25,76 @@ use crate::ecs::constants::{
     MEDIUM_COMPONENT_THRESHOLD,
 };
 
+const WORD_BITS: usize = u64::BITS as usize;
 
-/// Static component pool handling components of specific type
+/// Bitset of chunk indices
+#[derive(Default)]
+struct ChunkBits {
+    words: Vec<u64>,
+}
+
+impl ChunkBits {
+    #[inline]
+    fn insert(&mut self, index: usize) {
+        let word_index = index / WORD_BITS;
+        if word_index >= self.words.len() {
+            self.words.resize(word_index + 1, 0);
+        }
+        self.words[word_index] |= 1 << (index % WORD_BITS);
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) {
+        if let Some(word) = self.words.get_mut(index / WORD_BITS) {
+            *word &= !(1 << (index % WORD_BITS));
+        }
+    }
+
+    /// Lowest index in the set that is not below `start`
+    #[inline]
+    fn next_from(&self, start: usize) -> Option<usize> {
+        let mut word_index = start / WORD_BITS;
+        let mut word = self.words.get(word_index)? & (u64::MAX << (start % WORD_BITS));
+
+        loop {
+            if word != 0 {
+                return Some(word_index * WORD_BITS + word.trailing_zeros() as usize);
+            }
+
+            word_index += 1;
+            word = *self.words.get(word_index)?;
+        }
+    }
+}
+
+/// Component pool handling components of specific type
 ///
-/// Provides cache-friendly component storage using pre-allocated static chunks.
+/// Provides cache-friendly component storage using fixed-size chunks.
 /// Uses swap_remove strategy to maintain data densely packed within each chunk.
-/// All chunks are pre-allocated during initialization for maximum performance.
+/// New components go to the lowest chunk with free space, so slots freed by removals
+/// are reused and the chunks at the end empty out. Once more than `MAX_EMPTY_CHUNKS_RATIO`
+/// of the chunks are empty, empty chunks are returned to the allocator. When every chunk
+/// is full the pool grows by `GROWTH_FACTOR`. Chunks never change their index,
+/// so a `UnitId` stays valid while the pool grows and shrinks.
+///
+/// The chunk a component goes to depends only on the component counts, never on which
+/// chunks are allocated, so pools that see the same adds and removes hand out the same
+/// `UnitId`s. Archetype columns rely on that.
 pub struct ComponentPool<T: Component> {
-    /// Reference to the arena used for memory allocation
-    arena: NonNull<Arena>,
+    /// Allocator new chunks are taken from
+    allocator: Arc<dyn ChunkAllocator>,
+
+    /// Component chunks by index, `None` once an empty chunk was given back to the allocator
+    chunks: Vec<Option<Chunk<T>>>,
 
-    /// Vector of pre-allocated component chunks
-    chunks: Vec<Chunk<T>>,
+    /// Chunks with at least one free slot, released chunks included
+    free_chunks: ChunkBits,
 
-    /// Index of the current chunk for new component allocations
-    current_chunk_index: usize,
+    /// Number of allocated chunks without components
+    empty_chunks: usize,
+
+    /// Number of chunks given back to the allocator
+    released_chunks: usize,
 
     /// Number of active components in the pool
     count: usize,
@@ -41,39 +104,70 @@ pub struct ComponentPool<T: Component> {
 
     component_id: usize,
 
+    /// Storage the chunks are reported as in leak reports
+    tag: PoolTag,
+
     /// Component type marker
     _marker: PhantomData<T>,
 }
 
 impl<T: Component> ComponentPool<T> {
     /// Creates a new component pool with pre-allocated chunks
-    pub fn new(arena: &Arena, num_chunks: usize, components_per_chunk: usize) -> Self {
-        let mut chunks = Vec::with_capacity(num_chunks);
+    #[track_caller]
+    pub fn new(allocator: &Arc<dyn ChunkAllocator>, num_chunks: usize, components_per_chunk: usize) -> Self {
+        Self::try_new(allocator, num_chunks, components_per_chunk)
+            .unwrap_or_else(|error| panic!("Failed to allocate component pool: {error}"))
+    }
 
-        // Pre-allocate all chunks
-        for _ in 0..num_chunks {
-            chunks.push(Chunk::<T>::new(arena, components_per_chunk));
-        }
+    /// Creates a new component pool, failing instead of panicking if the allocator is out of memory
+    ///
+    /// Chunks allocated before the failure are returned to the allocator.
+    #[track_caller]
+    pub fn try_new(allocator: &Arc<dyn ChunkAllocator>, num_chunks: usize, components_per_chunk: usize) -> EcsResult<Self> {
+        Self::try_new_tagged(allocator, num_chunks, components_per_chunk, PoolTag::Standalone)
+    }
 
-        Self {
-            arena: NonNull::from(arena),
-            chunks,
-            current_chunk_index: 0,  // Start with the first chunk
+    /// Creates a new component pool whose chunks are reported as part of `tag`
+    #[track_caller]
+    pub fn try_new_tagged(
+        allocator: &Arc<dyn ChunkAllocator>,
+        num_chunks: usize,
+        components_per_chunk: usize,
+        tag: PoolTag,
+    ) -> EcsResult<Self> {
+        let mut pool = Self {
+            allocator: Arc::clone(allocator),
+            chunks: Vec::with_capacity(num_chunks),
+            free_chunks: ChunkBits::default(),
+            empty_chunks: 0,
+            released_chunks: 0,
             count: 0,
             capacity_per_chunk: components_per_chunk,
             component_id: T::component_id(),
+            tag,
             _marker: PhantomData,
-        }
+        };
+
+        // Pre-allocate all chunks
+        pool.allocate_chunks(num_chunks)?;
+        Ok(pool)
     }
 
     /// Creates a new component pool with default sizes based on component type
-    pub fn with_default_sizes(arena: &Arena) -> Self {
+    #[track_caller]
+    pub fn with_default_sizes(allocator: &Arc<dyn ChunkAllocator>) -> Self {
         let components_per_chunk = Self::get_optimal_chunk_capacity();
-        Self::new(arena, DEFAULT_CHUNKS_PER_POOL, components_per_chunk)
+        Self::new(allocator, DEFAULT_CHUNKS_PER_POOL, components_per_chunk)
     }
 
     /// Determines the optimal number of components per chunk based on component size
-    fn get_optimal_chunk_capacity() -> usize {
+    ///
+    /// An explicit `#[component(chunk_capacity = N)]` always wins over the size categories.
+    pub fn get_optimal_chunk_capacity() -> usize {
+        if let Some(capacity) = T::chunk_capacity() {
+            return capacity;
+        }
+
         let size = size_of::<T>();
         if size <= TINY_COMPONENT_THRESHOLD {
             TINY_COMPONENTS_PER_CHUNK
@@ -88,126 +182,343 @@ impl<T: Component> ComponentPool<T> {
 
     /// Adds a component to the pool, returning its index
     ///
-    /// O(1) implementation: Always adds to the current chunk,
-    /// moving to the next pre-allocated chunk when full.
+    /// O(1) implementation: adds to the lowest chunk with free space,
+    /// allocating a chunk only if that one was released or every chunk is full.
+    #[track_caller]
     pub fn add(&mut self, component: T) -> Option<UnitId> {
-        // Check if we have any chunks
-        if self.chunks.is_empty() {
-            return None;  // Pool is not properly initialized
-        }
+        self.try_add(component).ok()
+    }
 
-        // Get the current chunk
-        let chunk = &mut self.chunks[self.current_chunk_index];
+    /// Adds a component to the pool, returning `OutOfMemory` if the pool cannot grow
+    #[track_caller]
+    pub fn try_add(&mut self, component: T) -> EcsResult<UnitId> {
+        self.try_reserve(1)?;
 
-        // If the current chunk is full, move to the next one
-        if chunk.count() >= self.capacity_per_chunk {
-            self.current_chunk_index += 1;
+        let chunk_index = self.current_chunk_index();
+        let chunk = self.chunks[chunk_index].as_mut().expect("Reserved chunk is allocated");
+        let id_inland = chunk.try_add(component)?;
 
-            // Check if we've exhausted all chunks
-            if self.current_chunk_index >= self.chunks.len() {
-                // We've run out of pre-allocated chunks
-                return None;
-            }
+        if chunk.count() == 1 {
+            self.empty_chunks -= 1;
+        }
+        if chunk.count() == self.capacity_per_chunk {
+            self.free_chunks.remove(chunk_index);
         }
-
-        // Now we're pointing at a chunk with space, use it
-        let chunk = &mut self.chunks[self.current_chunk_index];
-        let id_inland = match chunk.add(component) {
-            Some(idx) => idx,
-            None => return None, // This shouldn't happen if capacity is respected
-        };
 
         self.count += 1;
-        Some(UnitId::new(self.current_chunk_index, id_inland))
+        Ok(UnitId::new(chunk_index, id_inland))
+    }
+
+    #[inline]
+    fn chunk(&self, chunk_index: usize) -> Option<&Chunk<T>> {
+        self.chunks.get(chunk_index)?.as_ref()
+    }
+
+    #[inline]
+    fn chunk_mut(&mut self, chunk_index: usize) -> Option<&mut Chunk<T>> {
+        self.chunks.get_mut(chunk_index)?.as_mut()
     }
 
     /// Gets a reference to a component by its index
     pub fn get(&self, index: UnitId) -> Option<&T> {
-        let chunk_index = index.id_chunk as usize;
-        if chunk_index >= self.chunks.len() {
-            return None;
-        }
-
-        let chunk = &self.chunks[chunk_index];
-        chunk.get(index.id_inland as usize)
+        self.chunk(index.id_chunk as usize)?.get(index.id_inland as usize)
     }
 
     /// Gets a mutable reference to a component by its index
     pub fn get_mut(&mut self, index: UnitId) -> Option<&mut T> {
-        let chunk_index = index.id_chunk as usize;
-        if chunk_index >= self.chunks.len() {
-            return None;
-        }
-
-        let chunk = &mut self.chunks[chunk_index];
-        chunk.get_mut(index.id_inland as usize)
+        self.chunk_mut(index.id_chunk as usize)?.get_mut(index.id_inland as usize)
     }
 
     /// Removes a component at the specified index using swap_remove strategy
     pub fn swap_remove(&mut self, index: UnitId) -> bool {
-        let chunk_index = index.id_chunk as usize;
-        if chunk_index >= self.chunks.len() {
-            return false;
-        }
+        self.try_swap_remove(index).is_ok()
+    }
 
-        let chunk = &mut self.chunks[chunk_index];
+    /// Removes a component at the specified index, returning `MissingComponent` for an empty slot
+    pub fn try_swap_remove(&mut self, index: UnitId) -> EcsResult<()> {
+        let component = self.swap_take(index).ok_or(EcsError::MissingComponent(T::name()))?;
 
-        // Try to remove the component from the chunk
-        if !chunk.swap_remove(index.id_inland as usize) {
-            return false;
-        }
+        // Dropped once the pool is consistent again, a panicking destructor cannot break it
+        drop(component);
+        Ok(())
+    }
+
+    /// Removes a component at the specified index using swap_remove strategy
+    /// and returns it instead of dropping it
+    pub fn swap_take(&mut self, index: UnitId) -> Option<T> {
+        let chunk_index = index.id_chunk as usize;
+        let chunk = self.chunk_mut(chunk_index)?;
+        let component = chunk.swap_take(index.id_inland as usize)?;
+        let is_empty = chunk.count() == 0;
 
         self.count -= 1;
-        true
+        self.free_chunks.insert(chunk_index);
+        if is_empty {
+            self.empty_chunks += 1;
+            if self.has_too_many_empty_chunks() {
+                self.release_empty_chunks();
+            }
+        }
+
+        Some(component)
+    }
+
+    /// Gets a raw pointer to a component by its index
+    ///
+    /// The pointer points into chunk memory, so writing through it is allowed
+    /// as long as no reference to the same component is alive
+    pub fn get_ptr(&self, index: UnitId) -> Option<NonNull<T>> {
+        self.chunk(index.id_chunk as usize)?.get_ptr(index.id_inland as usize)
     }
 
     /// Find all components in a chunk and return them as references
+    ///
+    /// A released chunk has no components.
     pub fn chunk_components(&self, chunk_index: usize) -> Option<&[T]> {
-        if chunk_index >= self.chunks.len() {
-            return None;
-        }
-
-        Some(self.chunks[chunk_index].as_slice())
+        let chunk = self.chunks.get(chunk_index)?;
+        Some(chunk.as_ref().map_or(&[], Chunk::as_slice))
     }
 
     /// Find all components in a chunk and return them as mutable references
     pub fn chunk_components_mut(&mut self, chunk_index: usize) -> Option<&mut [T]> {
-        if chunk_index >= self.chunks.len() {
-            return None;
-        }
-
-        Some(self.chunks[chunk_index].as_mut_slice())
+        let chunk = self.chunks.get_mut(chunk_index)?;
+        Some(match chunk {
+            Some(chunk) => chunk.as_mut_slice(),
+            None => &mut [],
+        })
     }
 
-    /// Gets the number of chunks in this pool
+    /// Gets the number of chunks in this pool, released ones included
     pub fn chunks_count(&self) -> usize {
         self.chunks.len()
     }
 
-    /// Gets the index of the current top chunk
+    /// Gets the number of chunks currently holding memory
+    pub fn allocated_chunks_count(&self) -> usize {
+        self.chunks.len() - self.released_chunks
+    }
+
+    /// Gets the index of the chunk the next component goes to
+    ///
+    /// Equal to `chunks_count()` if every chunk is full.
     pub fn current_chunk_index(&self) -> usize {
-        self.current_chunk_index
+        self.free_chunks.next_from(0).unwrap_or(self.chunks.len())
     }
 
     /// Gets the count of components in a specific chunk
     pub fn chunk_component_count(&self, chunk_index: usize) -> Option<usize> {
-        if chunk_index >= self.chunks.len() {
-            return None;
-        }
-
-        Some(self.chunks[chunk_index].count())
+        let chunk = self.chunks.get(chunk_index)?;
+        Some(chunk.as_ref().map_or(0, Chunk::count))
     }
 
-    /// Check if the pool is full (all chunks are at capacity)
+    /// Check if the next `add` has to allocate a chunk
     pub fn is_full(&self) -> bool {
-        self.current_chunk_index >= self.chunks.len() - 1 &&
-            self.chunks.last().map_or(true, |chunk| chunk.count() >= self.capacity_per_chunk)
+        self.chunk(self.current_chunk_index()).is_none()
     }
 
-    /// Gets the remaining capacity in the pool
+    /// Gets the number of free slots in the allocated chunks
     pub fn remaining_capacity(&self) -> usize {
-        let total_capacity = self.chunks.len() * self.capacity_per_chunk;
-        total_capacity - self.count
+        self.allocated_chunks_count() * self.capacity_per_chunk - self.count
+    }
+
+    /// Makes sure `additional` components can be added without allocating
+    ///
+    /// Allocates the released chunks the components will go to and grows the pool
+    /// by `GROWTH_FACTOR`, or more if that is not enough. Chunks allocated before
+    /// a failure are kept.
+    #[track_caller]
+    pub fn try_reserve(&mut self, additional: usize) -> EcsResult<()> {
+        let mut available = 0;
+        let mut next = self.free_chunks.next_from(0);
+
+        while available < additional {
+            let Some(chunk_index) = next else {
+                return self.grow(additional - available);
+            };
+
+            let count = match &self.chunks[chunk_index] {
+                Some(chunk) => chunk.count(),
+                None => {
+                    self.chunks[chunk_index] = Some(Chunk::try_new_in_pool(&self.allocator, self.capacity_per_chunk, self.tag)?);
+                    self.released_chunks -= 1;
+                    self.empty_chunks += 1;
+                    0
+                }
+            };
+
+            available += self.capacity_per_chunk - count;
+            next = self.free_chunks.next_from(chunk_index + 1);
+        }
+
+        Ok(())
+    }
+
+    /// Checks if compacting would free more than `COMPACTION_THRESHOLD` of the chunks in use
+    ///
+    /// Pools with fewer than `MIN_COMPONENTS_FOR_COMPACTION` components are never compacted.
+    pub fn needs_compaction(&self) -> bool {
+        if self.count < MIN_COMPONENTS_FOR_COMPACTION || self.capacity_per_chunk == 0 {
+            return false;
+        }
+
+        let used_chunks = self.allocated_chunks_count() - self.empty_chunks;
+        let dense_chunks = self.count.div_ceil(self.capacity_per_chunk);
+        (used_chunks - dense_chunks) as f32 / used_chunks as f32 > COMPACTION_THRESHOLD
+    }
+
+    /// Moves components out of the emptiest chunks into the fullest ones
+    ///
+    /// Afterwards at most one chunk is partially filled. Components are taken from
+    /// the end of their chunk, so only the moved ones change their index. Returns
+    /// the moved components as `(old, new)` pairs for the caller to update its index.
+    ///
+    /// Which components move depends only on the chunk counts, so pools that saw
+    /// the same adds and removes move the same components.
+    pub fn compact(&mut self) -> Vec<(UnitId, UnitId)> {
+        // Partially filled chunks, fullest first; ties keep the lower chunk as a target
+        let mut partial: Vec<(usize, usize)> = self.chunks.iter().enumerate()
+            .filter_map(|(index, chunk)| Some((index, chunk.as_ref()?.count())))
+            .filter(|&(_, count)| count > 0 && count < self.capacity_per_chunk)
+            .collect();
+        partial.sort_by_key(|&(index, count)| (std::cmp::Reverse(count), index));
+
+        let mut remap = Vec::new();
+        let (mut target, mut source) = (0, partial.len().saturating_sub(1));
+        while target < source {
+            let (target_index, source_index) = (partial[target].0, partial[source].0);
+
+            let source_chunk = self.chunks[source_index].as_mut().expect("Partial chunk is allocated");
+            let old_inland = source_chunk.count() - 1;
+            let component = source_chunk.swap_take(old_inland).expect("Chunk holds its last component");
+            let source_is_empty = source_chunk.count() == 0;
+
+            let target_chunk = self.chunks[target_index].as_mut().expect("Partial chunk is allocated");
+            let new_inland = target_chunk.try_add(component)
+                .unwrap_or_else(|_| unreachable!("Target chunk has free space"));
+            let target_is_full = target_chunk.count() == self.capacity_per_chunk;
+
+            remap.push((UnitId::new(source_index, old_inland), UnitId::new(target_index, new_inland)));
+
+            if target_is_full {
+                self.free_chunks.remove(target_index);
+                target += 1;
+            }
+            if source_is_empty {
+                self.empty_chunks += 1;
+                source -= 1;
+            }
+        }
+
+        if self.has_too_many_empty_chunks() {
+            self.release_empty_chunks();
+        }
+
+        remap
+    }
+
+    /// Appends enough chunks for `additional` components, at least `GROWTH_FACTOR` times more
+    #[cold]
+    #[track_caller]
+    fn grow(&mut self, additional: usize) -> EcsResult<()> {
+        if self.capacity_per_chunk == 0 {
+            return Err(EcsError::CapacityExceeded { capacity: 0 });
+        }
+
+        let needed = additional.div_ceil(self.capacity_per_chunk);
+        let grown = ((self.chunks.len() as f32 * GROWTH_FACTOR) as usize).saturating_sub(self.chunks.len());
+        self.allocate_chunks(needed.max(grown))
+    }
+
+    #[track_caller]
+    fn allocate_chunks(&mut self, num_chunks: usize) -> EcsResult<()> {
+        self.chunks.reserve(num_chunks);
+
+        for _ in 0..num_chunks {
+            let chunk = Chunk::try_new_in_pool(&self.allocator, self.capacity_per_chunk, self.tag)?;
+            if self.capacity_per_chunk > 0 {
+                self.free_chunks.insert(self.chunks.len());
+            }
+
+            self.chunks.push(Some(chunk));
+            self.empty_chunks += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Checks if more than `MAX_EMPTY_CHUNKS_RATIO` of the allocated chunks are empty
+    ///
+    /// One empty chunk is always kept, so a pool that goes back and forth across
+    /// a chunk boundary does not allocate on every add.
+    #[inline]
+    fn has_too_many_empty_chunks(&self) -> bool {
+        self.empty_chunks > 1
+            && self.empty_chunks as f32 > self.allocated_chunks_count() as f32 * MAX_EMPTY_CHUNKS_RATIO
+    }
+
+    /// Gives empty chunks back to the allocator until the ratio is met again
+    fn release_empty_chunks(&mut self) {
+        // Components gather in the lowest chunks, so the last empty chunks are refilled last
+        let mut chunk_index = self.chunks.len();
+        while chunk_index > 0 && self.has_too_many_empty_chunks() {
+            chunk_index -= 1;
+
+            let slot = &mut self.chunks[chunk_index];
+            if slot.as_ref().is_some_and(|chunk| chunk.count() == 0) {
+                *slot = None;
+                self.empty_chunks -= 1;
+                self.released_chunks += 1;
+            }
+        }
+    }
+}
+
+/// Type-erased view of a component pool
+///
+/// Archetype tables keep one pool per component type and drive all of them
+/// through this trait, so every column sees the same sequence of operations
+/// and a row has the same `UnitId` in each of them.
+pub trait ComponentStorage {
+    /// ID of the stored component type
+    fn component_id(&self) -> ComponentId;
+
+    fn component_type_id(&self) -> TypeId;
+
+    fn component_size(&self) -> usize;
+
+    /// Number of active components
+    fn count(&self) -> usize;
+
+    /// Total number of component slots in the allocated chunks
+    fn capacity(&self) -> usize;
+
+    /// Grows the storage until `additional` components fit, see `ComponentPool::try_reserve`
+    #[track_caller]
+    fn try_reserve(&mut self, additional: usize) -> EcsResult<()>;
+
+    /// Merges sparse chunks, see `ComponentPool::compact`
+    fn compact(&mut self) -> Vec<(UnitId, UnitId)>;
+
+    /// Removes and drops the component at the specified index
+    fn swap_remove(&mut self, index: UnitId) -> bool;
+
+    /// Moves the component at the specified index into `target`
+    ///
+    /// `target` must store the same component type.
+    #[track_caller]
+    fn move_to(&mut self, index: UnitId, target: &mut dyn ComponentStorage) -> Option<UnitId>;
+
+    /// Points the chunks moved by an arena compaction to their new memory
+    fn relocate(&mut self, relocations: &Relocations);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> ComponentStorage for ComponentPool<T> {
+    fn component_id(&self) -> ComponentId {
+        self.component_id
     }
 
     fn component_type_id(&self) -> TypeId {
@@ -223,7 +534,45 @@ impl<T: Component> ComponentPool<T> {
     }
 
     fn capacity(&self) -> usize {
-        self.chunks.len() * self.capacity_per_chunk
+        self.allocated_chunks_count() * self.capacity_per_chunk
+    }
+
+    #[track_caller]
+    fn try_reserve(&mut self, additional: usize) -> EcsResult<()> {
+        ComponentPool::try_reserve(self, additional)
+    }
+
+    fn compact(&mut self) -> Vec<(UnitId, UnitId)> {
+        ComponentPool::compact(self)
+    }
+
+    fn swap_remove(&mut self, index: UnitId) -> bool {
+        ComponentPool::swap_remove(self, index)
+    }
+
+    #[track_caller]
+    fn move_to(&mut self, index: UnitId, target: &mut dyn ComponentStorage) -> Option<UnitId> {
+        let target = target.as_any_mut().downcast_mut::<ComponentPool<T>>()
+            .expect("Component storages of different types");
+
+        // Make room in the target first, so the component is never taken out and lost
+        target.try_reserve(1).ok()?;
+
+        let component = self.swap_take(index)?;
+        target.add(component)
     }
 
+    fn relocate(&mut self, relocations: &Relocations) {
+        for chunk in self.chunks.iter_mut().flatten() {
+            chunk.relocate(relocations);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
@@ -0,0 +1,202 @@
+use std::alloc::Layout;
+use std::ptr::NonNull;
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+use crate::ecs::constants::{DEBUG_ALLOC_BYTE, DEBUG_CANARY_BYTE, DEBUG_CANARY_SIZE, DEBUG_POISON_BYTE};
+#[cfg(target_os = "linux")]
+use crate::ecs::constants::DEBUG_GUARD_PAGE_THRESHOLD;
+use crate::ecs::error::{EcsError, EcsResult};
+#[cfg(target_os = "linux")]
+use crate::ecs::memory::mmap::MmapRegion;
+use crate::ecs::memory::utils::align_up;
+
+/// Own mapping of an allocation that ends right before a guard page
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy)]
+struct GuardedMapping {
+    base: usize,
+
+    len: usize,
+
+    /// Start of the guard page, everything between the allocation and it is canary
+    guard: usize,
+}
+
+/// Out-of-bounds detection for arena allocations, enabled by the `debug-arena` feature
+///
+/// Every allocation gets canary bytes right before and after it, which are checked
+/// when it is freed and by `Arena::validate`. New memory is filled with
+/// `DEBUG_ALLOC_BYTE` and freed memory with `DEBUG_POISON_BYTE`, so reads of
+/// uninitialized or freed memory stand out. With guard pages, large allocations get
+/// a mapping of their own that ends at an inaccessible page, so an overrun faults
+/// on the spot instead of being found later.
+pub(crate) struct DebugArena {
+    #[cfg(target_os = "linux")]
+    guard_pages: bool,
+
+    /// Guarded allocations by address
+    #[cfg(target_os = "linux")]
+    guarded: Mutex<HashMap<usize, GuardedMapping>>,
+}
+
+impl DebugArena {
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    pub(crate) fn new(guard_pages: bool) -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            guard_pages,
+            #[cfg(target_os = "linux")]
+            guarded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Allocates the layout with canaries around it, taking the padded block from `allocate`
+    pub(crate) fn allocate(
+        &self,
+        layout: Layout,
+        allocate: impl FnOnce(Layout) -> EcsResult<NonNull<u8>>,
+    ) -> EcsResult<NonNull<u8>> {
+        #[cfg(target_os = "linux")]
+        if self.guard_pages
+            && layout.size() >= DEBUG_GUARD_PAGE_THRESHOLD
+            && let Some(ptr) = self.allocate_guarded(layout)?
+        {
+            return Ok(ptr);
+        }
+
+        let (padded, front) = Self::padded_layout(layout)?;
+        let base = allocate(padded)?;
+
+        unsafe {
+            base.as_ptr().write_bytes(DEBUG_CANARY_BYTE, front);
+            base.as_ptr().add(front).write_bytes(DEBUG_ALLOC_BYTE, layout.size());
+            base.as_ptr().add(front + layout.size()).write_bytes(DEBUG_CANARY_BYTE, DEBUG_CANARY_SIZE);
+            Ok(base.add(front))
+        }
+    }
+
+    /// Checks the canaries, poisons the block and hands the padded block to `deallocate`
+    ///
+    /// A damaged block is reported and never freed, so the evidence stays in place.
+    pub(crate) fn deallocate(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        deallocate: impl FnOnce(NonNull<u8>, Layout),
+    ) -> EcsResult<()> {
+        self.check(ptr, layout)?;
+
+        #[cfg(target_os = "linux")]
+        if let Some(mapping) = self.guarded().remove(&(ptr.as_ptr() as usize)) {
+            unsafe { libc::munmap(mapping.base as *mut libc::c_void, mapping.len) };
+            return Ok(());
+        }
+
+        let (padded, front) = Self::padded_layout(layout)?;
+        unsafe {
+            let base = ptr.sub(front);
+            base.as_ptr().write_bytes(DEBUG_POISON_BYTE, padded.size());
+            deallocate(base, padded);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the canaries around a live allocation are intact
+    pub(crate) fn check(&self, ptr: NonNull<u8>, layout: Layout) -> EcsResult<()> {
+        let address = ptr.as_ptr() as usize;
+        let damaged = EcsError::MemoryCorruption { address };
+
+        #[cfg(target_os = "linux")]
+        if let Some(mapping) = self.guarded().get(&address).copied() {
+            let slack = mapping.guard - (address + layout.size());
+            let intact = unsafe { Self::is_canary(ptr.as_ptr().add(layout.size()), slack) };
+            return if intact { Ok(()) } else { Err(damaged) };
+        }
+
+        let (_, front) = Self::padded_layout(layout)?;
+        let intact = unsafe {
+            Self::is_canary(ptr.as_ptr().sub(front), front)
+                && Self::is_canary(ptr.as_ptr().add(layout.size()), DEBUG_CANARY_SIZE)
+        };
+
+        if intact { Ok(()) } else { Err(damaged) }
+    }
+
+    /// Block with room for the canaries and the offset of the allocation inside it
+    fn padded_layout(layout: Layout) -> EcsResult<(Layout, usize)> {
+        let front = align_up(DEBUG_CANARY_SIZE, layout.align());
+        let size = front.checked_add(layout.size())
+            .and_then(|size| size.checked_add(DEBUG_CANARY_SIZE))
+            .ok_or(EcsError::InvalidLayout)?;
+
+        Ok((Layout::from_size_align(size, layout.align())?, front))
+    }
+
+    unsafe fn is_canary(ptr: *const u8, len: usize) -> bool {
+        unsafe { std::slice::from_raw_parts(ptr, len) }.iter().all(|&byte| byte == DEBUG_CANARY_BYTE)
+    }
+
+    /// Maps the allocation so it ends as close to a guard page as its alignment allows,
+    /// returns `None` if the alignment is stricter than a page
+    #[cfg(target_os = "linux")]
+    fn allocate_guarded(&self, layout: Layout) -> EcsResult<Option<NonNull<u8>>> {
+        let page_size = MmapRegion::system_page_size();
+        if layout.align() > page_size {
+            return Ok(None);
+        }
+
+        let out_of_memory = EcsError::OutOfMemory { size: layout.size(), align: layout.align() };
+        let data_len = align_up(layout.size(), page_size);
+        let len = data_len.checked_add(page_size).ok_or(out_of_memory)?;
+
+        let base_ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if base_ptr == libc::MAP_FAILED {
+            return Err(out_of_memory);
+        }
+
+        let base = base_ptr as usize;
+        let guard = base + data_len;
+        if unsafe { libc::mprotect(guard as *mut libc::c_void, page_size, libc::PROT_NONE) } != 0 {
+            unsafe { libc::munmap(base as *mut libc::c_void, len) };
+            return Err(out_of_memory);
+        }
+
+        let start = (guard - layout.size()) & !(layout.align() - 1);
+        let ptr = unsafe { base_ptr.cast::<u8>().add(start - base) };
+        unsafe {
+            ptr.write_bytes(DEBUG_ALLOC_BYTE, layout.size());
+            ptr.add(layout.size()).write_bytes(DEBUG_CANARY_BYTE, guard - start - layout.size());
+        }
+
+        self.guarded().insert(start, GuardedMapping { base, len, guard });
+        Ok(NonNull::new(ptr))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn guarded(&self) -> std::sync::MutexGuard<'_, HashMap<usize, GuardedMapping>> {
+        self.guarded.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for DebugArena {
+    fn drop(&mut self) {
+        // Leaked guarded allocations, they already show up in the leak report
+        let guarded = self.guarded.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for mapping in guarded.values() {
+            unsafe { libc::munmap(mapping.base as *mut libc::c_void, mapping.len) };
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, HashMap};
+use crate::ecs::constants::MIN_BLOCK_ALIGN;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MemFreeBlock {
@@ -19,6 +20,33 @@ impl MemFreeBlock {
     }
 }
 
+/// Bookkeeping of the free space of an arena region
+///
+/// Offsets are relative to the region start, the memory itself is never touched.
+pub trait FreeBlockPolicy: Send {
+    /// Adds a free block, merging it with adjacent free blocks
+    fn insert(&mut self, block: MemFreeBlock);
+
+    /// Takes a block of `size` bytes whose address is a multiple of `align`
+    ///
+    /// `base` is the address of offset 0, regions are not aligned to every possible `align`.
+    fn allocate_aligned(&mut self, size: usize, align: usize, base: usize) -> Option<MemFreeBlock>;
+
+    /// Takes `size` bytes from the front of the free block that starts at `start`
+    fn take_front(&mut self, start: usize, size: usize) -> bool;
+
+    /// Number of free blocks
+    fn block_count(&self) -> usize;
+
+    fn total_free_size(&self) -> usize;
+
+    /// Size of the largest free block, 0 if there is none
+    fn largest_free_block(&self) -> usize;
+
+    /// All free blocks in no particular order
+    fn blocks(&self) -> Vec<MemFreeBlock>;
+}
+
 pub struct MemFreeBlockMaster {
     blocks: Vec<MemFreeBlock>,
 
@@ -86,6 +114,28 @@ impl MemFreeBlockMaster {
         self.size += 1;
     }
 
+    /// Takes `size` bytes from the front of the free block that starts at `start`
+    ///
+    /// Lets an allocation ending at `start` grow in place, returns false if there
+    /// is no such block or it is too small.
+    pub fn take_front(&mut self, start: usize, size: usize) -> bool {
+        let Some(&index) = self.start_map.get(&start) else {
+            return false;
+        };
+
+        let block = self.blocks[index];
+        if block.size() < size {
+            return false;
+        }
+
+        self.remove_block_index(index);
+        if block.size() > size {
+            self.insert(MemFreeBlock::new(start + size, block.end));
+        }
+
+        true
+    }
+
     fn try_merge_remove(&mut self, mut block: MemFreeBlock) -> MemFreeBlock {
 
         if let Some(&left_index) = self.end_map.get(&block.start) {
@@ -161,20 +211,20 @@ impl MemFreeBlockMaster {
         Some(block)
     }
 
-    /// Выделяет выровненный блок памяти
-    pub fn allocate_aligned(&mut self, size: usize, align: usize) -> Option<MemFreeBlock> {
+    /// Выделяет выровненный блок памяти, `base` - адрес нулевого смещения
+    pub fn allocate_aligned(&mut self, size: usize, align: usize, base: usize) -> Option<MemFreeBlock> {
         if size == 0 {
             return None;
         }
 
-        // Ищем блок с учетом максимально возможного выравнивания
-        let required_size = size + align - 1;
+        // Начала блоков кратны MIN_BLOCK_ALIGN, запас нужен только под большее выравнивание
+        let required_size = size + align.saturating_sub(MIN_BLOCK_ALIGN);
         let (block_index, block) = self.find_best_fit_with_index(required_size)?;
 
         self.remove_block_index(block_index);
 
-        // Вычисляем выровненный адрес начала
-        let aligned_start = crate::ecs::memory::utils::align_up(block.start, align);
+        // Вычисляем выровненный адрес начала, выравнивается сам адрес, а не смещение
+        let aligned_start = crate::ecs::memory::utils::align_up(base + block.start, align) - base;
 
         // Создаем выровненный блок
         let aligned_block = MemFreeBlock::new(aligned_start, aligned_start + size);
@@ -283,6 +333,36 @@ impl MemFreeBlockMaster {
     }
 }
 
+impl FreeBlockPolicy for MemFreeBlockMaster {
+    fn insert(&mut self, block: MemFreeBlock) {
+        MemFreeBlockMaster::insert(self, block);
+    }
+
+    fn allocate_aligned(&mut self, size: usize, align: usize, base: usize) -> Option<MemFreeBlock> {
+        MemFreeBlockMaster::allocate_aligned(self, size, align, base)
+    }
+
+    fn take_front(&mut self, start: usize, size: usize) -> bool {
+        MemFreeBlockMaster::take_front(self, start, size)
+    }
+
+    fn block_count(&self) -> usize {
+        self.len()
+    }
+
+    fn total_free_size(&self) -> usize {
+        MemFreeBlockMaster::total_free_size(self)
+    }
+
+    fn largest_free_block(&self) -> usize {
+        self.mem_size_tree.last_key_value().map_or(0, |(&size, _)| size)
+    }
+
+    fn blocks(&self) -> Vec<MemFreeBlock> {
+        self.mem_size_tree.values().flatten().map(|&index| self.blocks[index]).collect()
+    }
+}
+
 pub struct MemoryStats {
     pub active_blocks: usize,
     pub total_blocks: usize,
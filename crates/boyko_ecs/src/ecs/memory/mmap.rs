@@ -0,0 +1,143 @@
+use std::ptr::NonNull;
+use crate::ecs::constants::{HUGE_PAGE_SIZE, MMAP_RELEASE_THRESHOLD};
+use crate::ecs::error::{EcsError, EcsResult};
+use crate::ecs::memory::utils::align_up;
+
+/// How an mmap arena region uses huge pages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HugePages {
+    /// Regular pages only
+    #[default]
+    Off,
+
+    /// Ask the kernel for transparent huge pages with `madvise(MADV_HUGEPAGE)`
+    Transparent,
+
+    /// Map explicit huge pages with `MAP_HUGETLB`, needs pages reserved in the system
+    Explicit,
+}
+
+/// Virtual address range reserved with `mmap` and committed page by page
+///
+/// The whole range is reserved as inaccessible memory up front. Pages become
+/// readable and writable once a block inside them is handed out, growing the
+/// committed prefix, and large freed blocks give their pages back to the OS.
+pub(crate) struct MmapRegion {
+    ptr: NonNull<u8>,
+
+    reserved: usize,
+
+    /// Length of the committed prefix of the range
+    committed: usize,
+
+    /// Granularity of commits and releases
+    page_size: usize,
+}
+
+impl MmapRegion {
+    /// Reserves at least `size` bytes of address space
+    pub(crate) fn reserve(size: usize, huge_pages: HugePages) -> EcsResult<Self> {
+        let page_size = match huge_pages {
+            HugePages::Off => Self::system_page_size(),
+            HugePages::Transparent | HugePages::Explicit => HUGE_PAGE_SIZE,
+        };
+        let reserved = align_up(size, page_size);
+
+        let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE;
+        if huge_pages == HugePages::Explicit {
+            flags |= libc::MAP_HUGETLB;
+        }
+
+        let ptr = unsafe { libc::mmap(std::ptr::null_mut(), reserved, libc::PROT_NONE, flags, -1, 0) };
+        if ptr == libc::MAP_FAILED {
+            return Err(EcsError::OutOfMemory { size: reserved, align: page_size });
+        }
+
+        if huge_pages == HugePages::Transparent {
+            // Only a hint, the range works with regular pages as well
+            unsafe { libc::madvise(ptr, reserved, libc::MADV_HUGEPAGE) };
+        }
+
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).expect("mmap never returns null on success"),
+            reserved,
+            committed: 0,
+            page_size,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> NonNull<u8> {
+        self.ptr
+    }
+
+    #[inline]
+    pub(crate) fn reserved(&self) -> usize {
+        self.reserved
+    }
+
+    /// Number of bytes made accessible so far
+    #[inline]
+    pub(crate) fn committed(&self) -> usize {
+        self.committed
+    }
+
+    /// Makes everything below `end` accessible, returns false if the kernel refuses
+    pub(crate) fn commit_to(&mut self, end: usize) -> bool {
+        if end <= self.committed {
+            return true;
+        }
+
+        let new_committed = align_up(end, self.page_size).min(self.reserved);
+        let result = unsafe {
+            libc::mprotect(
+                self.ptr.as_ptr().add(self.committed).cast(),
+                new_committed - self.committed,
+                libc::PROT_READ | libc::PROT_WRITE,
+            )
+        };
+
+        if result != 0 {
+            return false;
+        }
+
+        self.committed = new_committed;
+        true
+    }
+
+    /// Gives the whole pages inside a freed block back to the OS
+    ///
+    /// The pages stay committed and read as zeros when they are touched again.
+    pub(crate) fn release(&self, start: usize, end: usize) {
+        if end - start < MMAP_RELEASE_THRESHOLD {
+            return;
+        }
+
+        let first_page = align_up(start, self.page_size);
+        let last_page = (end.min(self.committed) / self.page_size) * self.page_size;
+        if first_page >= last_page {
+            return;
+        }
+
+        unsafe {
+            libc::madvise(self.ptr.as_ptr().add(first_page).cast(), last_page - first_page, libc::MADV_DONTNEED);
+        }
+    }
+
+    /// Size of a regular page
+    pub(crate) fn system_page_size() -> usize {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        usize::try_from(page_size).unwrap_or(4096)
+    }
+}
+
+impl Drop for MmapRegion {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.reserved);
+        }
+    }
+}
+
+// The region is plain memory, the arena serializes access to it
+unsafe impl Send for MmapRegion {}
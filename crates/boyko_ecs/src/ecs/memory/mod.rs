@@ -3,7 +3,25 @@ pub mod arena;
 
 pub mod utils;
 mod free_mem_block;
+mod tlsf;
 pub mod chunk;
+pub mod chunk_allocator;
 pub mod component_pool;
 pub mod component_index;
+pub mod sparse_set;
+pub mod scratch;
+pub mod sub_arena;
+pub mod arena_box;
+pub mod arena_vec;
+pub mod arena_str;
+pub mod arena_stats;
+pub mod allocation_tracker;
 
+#[cfg(target_os = "linux")]
+pub mod mmap;
+
+#[cfg(feature = "debug-arena")]
+mod debug_arena;
+
+#[cfg(feature = "allocator-api2")]
+pub mod allocator;
@@ -0,0 +1,166 @@
+use std::alloc::Layout;
+use std::cell::Cell;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::ecs::error::{EcsError, EcsResult};
+use crate::ecs::memory::utils::align_up;
+
+/// Bump-allocated block of arena memory for temporary per-frame data
+///
+/// Allocation only moves the cursor forward, nothing is freed individually.
+/// The whole region is released at once by resetting the cursor.
+pub(crate) struct ScratchRegion {
+    ptr: NonNull<u8>,
+
+    capacity: usize,
+
+    /// Offset of the first free byte
+    cursor: AtomicUsize,
+}
+
+impl ScratchRegion {
+    pub(crate) fn new(ptr: NonNull<u8>, capacity: usize) -> Self {
+        Self { ptr, capacity, cursor: AtomicUsize::new(0) }
+    }
+
+    pub(crate) fn as_ptr(&self) -> NonNull<u8> {
+        self.ptr
+    }
+
+    /// Number of bytes allocated since the last reset
+    pub(crate) fn used(&self) -> usize {
+        self.cursor.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn alloc(&self, layout: Layout) -> EcsResult<NonNull<u8>> {
+        let (ptr, _) = self.bump(layout, None)?.expect("Unconditional bump always succeeds");
+        Ok(ptr)
+    }
+
+    /// Moves the cursor past a block for `layout`
+    ///
+    /// With `expected` the block is only placed right at that offset. Returns the block
+    /// and the new cursor, or `None` if `expected` was outdated.
+    fn bump(&self, layout: Layout, expected: Option<usize>) -> EcsResult<Option<(NonNull<u8>, usize)>> {
+        let base = self.ptr.as_ptr() as usize;
+        let out_of_memory = EcsError::OutOfMemory { size: layout.size(), align: layout.align() };
+
+        let mut current = self.cursor.load(Ordering::Acquire);
+        loop {
+            if expected.is_some_and(|expected| expected != current) {
+                return Ok(None);
+            }
+
+            let start = align_up(base + current, layout.align()) - base;
+            let end = start.checked_add(layout.size()).filter(|&end| end <= self.capacity)
+                .ok_or(out_of_memory)?;
+
+            match self.cursor.compare_exchange_weak(current, end, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return Ok(Some((unsafe { self.ptr.add(start) }, end))),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Moves the cursor back to `mark` if nothing was allocated after `end`
+    fn rewind(&self, end: usize, mark: usize) {
+        let _ = self.cursor.compare_exchange(end, mark, Ordering::AcqRel, Ordering::Acquire);
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self.cursor.get_mut() = 0;
+    }
+
+    /// Same as `reset` through a shared reference, no allocation may be in use
+    pub(crate) unsafe fn reset_unchecked(&self) {
+        self.cursor.store(0, Ordering::Release);
+    }
+}
+
+// The region is plain arena memory, the cursor is atomic
+unsafe impl Send for ScratchRegion {}
+unsafe impl Sync for ScratchRegion {}
+
+/// Scope of scratch allocations that are released when it is dropped
+///
+/// Values allocated through the scope are borrowed from it and cannot outlive it.
+/// On drop the cursor moves back to where the scope started, unless another scope
+/// or thread has allocated after it in the meantime; that memory is then reclaimed
+/// by the next `reset_scratch` instead. Destructors of scratch values never run.
+pub struct ScratchScope<'a> {
+    region: &'a ScratchRegion,
+
+    /// Cursor when the scope was opened
+    mark: usize,
+
+    /// Cursor after the last allocation of this scope
+    end: Cell<usize>,
+
+    /// Cleared once someone else allocated between the scope's allocations
+    contiguous: Cell<bool>,
+}
+
+impl<'a> ScratchScope<'a> {
+    pub(crate) fn new(region: &'a ScratchRegion) -> Self {
+        let mark = region.used();
+        Self { region, mark, end: Cell::new(mark), contiguous: Cell::new(true) }
+    }
+
+    /// Moves the value into scratch memory
+    #[allow(clippy::mut_from_ref)] // every call hands out a distinct block
+    pub fn alloc<T>(&self, value: T) -> EcsResult<&mut T> {
+        let ptr = self.alloc_layout(Layout::new::<T>())?.cast::<T>();
+
+        unsafe {
+            ptr.as_ptr().write(value);
+            Ok(&mut *ptr.as_ptr())
+        }
+    }
+
+    /// Copies the slice into scratch memory
+    #[allow(clippy::mut_from_ref)] // every call hands out a distinct block
+    pub fn alloc_slice_copy<T: Copy>(&self, values: &[T]) -> EcsResult<&mut [T]> {
+        let ptr = self.alloc_layout(Layout::array::<T>(values.len())?)?.cast::<T>();
+
+        unsafe {
+            ptr.as_ptr().copy_from_nonoverlapping(values.as_ptr(), values.len());
+            Ok(std::slice::from_raw_parts_mut(ptr.as_ptr(), values.len()))
+        }
+    }
+
+    /// Allocates uninitialized scratch memory for the layout
+    pub fn alloc_layout(&self, layout: Layout) -> EcsResult<NonNull<u8>> {
+        if layout.size() == 0 {
+            return Ok(NonNull::new(std::ptr::without_provenance_mut(layout.align()))
+                .expect("Alignment is never zero"));
+        }
+
+        if self.contiguous.get() {
+            if let Some((ptr, end)) = self.region.bump(layout, Some(self.end.get()))? {
+                self.end.set(end);
+                return Ok(ptr);
+            }
+
+            // Someone else allocated in between, rewinding would free their memory
+            self.contiguous.set(false);
+        }
+
+        self.region.alloc(layout)
+    }
+
+    /// Opens a nested scope, which is released before this one
+    ///
+    /// Allocating from this scope while the nested one is alive is allowed,
+    /// this scope just stops rewinding then.
+    pub fn scope(&self) -> ScratchScope<'_> {
+        ScratchScope::new(self.region)
+    }
+}
+
+impl Drop for ScratchScope<'_> {
+    fn drop(&mut self) {
+        if self.contiguous.get() {
+            self.region.rewind(self.end.get(), self.mark);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::any::Any;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use crate::ecs::core::component::{Component, ComponentId};
+use crate::ecs::core::entity::{Entity, EntityId};
+use crate::ecs::memory::allocation_tracker::PoolTag;
+use crate::ecs::memory::arena::Relocations;
+use crate::ecs::memory::chunk::Chunk;
+use crate::ecs::memory::chunk_allocator::ChunkAllocator;
+use crate::ecs::memory::component_pool::ComponentPool;
+use crate::ecs::constants::MAX_EMPTY_CHUNKS_RATIO;
+use crate::ecs::error::{EcsError, EcsResult};
+
+/// Marker for entity IDs that have no component in the set
+const EMPTY_SLOT: u32 = u32::MAX;
+
+/// Sparse-set storage for components that are added and removed frequently
+///
+/// Components are kept densely packed in chunks, while a sparse vector indexed
+/// by `EntityId` points into the dense array. Insert and remove are O(1) and never
+/// move the entity between archetypes.
+pub struct SparseSet<T: Component> {
+    /// Allocator new chunks are taken from
+    allocator: Arc<dyn ChunkAllocator>,
+
+    /// Dense component storage, index `i` lives in chunk `i / capacity_per_chunk`
+    chunks: Vec<Chunk<T>>,
+
+    /// Owner of each dense slot
+    entities: Vec<Entity>,
+
+    /// Dense index for every entity ID, `EMPTY_SLOT` if the entity has no component
+    sparse: Vec<u32>,
+
+    /// Number of components each chunk can hold
+    capacity_per_chunk: usize,
+}
+
+impl<T: Component> SparseSet<T> {
+    #[track_caller]
+    pub fn new(allocator: &Arc<dyn ChunkAllocator>, components_per_chunk: usize) -> Self {
+        Self::try_new(allocator, components_per_chunk)
+            .unwrap_or_else(|error| panic!("Failed to create sparse set: {error}"))
+    }
+
+    /// Creates an empty sparse set, failing if a chunk cannot hold a single component
+    pub fn try_new(allocator: &Arc<dyn ChunkAllocator>, components_per_chunk: usize) -> EcsResult<Self> {
+        if components_per_chunk == 0 {
+            return Err(EcsError::InvalidLayout);
+        }
+
+        Ok(Self {
+            allocator: Arc::clone(allocator),
+            chunks: Vec::new(),
+            entities: Vec::new(),
+            sparse: Vec::new(),
+            capacity_per_chunk: components_per_chunk,
+        })
+    }
+
+    /// Creates a sparse set with the chunk size picked for the component type
+    pub fn with_default_sizes(allocator: &Arc<dyn ChunkAllocator>) -> Self {
+        Self::new(allocator, ComponentPool::<T>::get_optimal_chunk_capacity())
+    }
+
+    #[inline]
+    fn dense_index(&self, id: EntityId) -> Option<usize> {
+        match self.sparse.get(id as usize) {
+            Some(&index) if index != EMPTY_SLOT => Some(index as usize),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn split_index(&self, dense_index: usize) -> (usize, usize) {
+        (dense_index / self.capacity_per_chunk, dense_index % self.capacity_per_chunk)
+    }
+
+    /// Inserts a component for the entity, returning the previous one if there was any
+    #[track_caller]
+    pub fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        self.try_insert(entity, component)
+            .unwrap_or_else(|error| panic!("Failed to insert sparse component: {error}"))
+    }
+
+    /// Inserts a component for the entity, failing if a new chunk cannot be allocated
+    #[track_caller]
+    pub fn try_insert(&mut self, entity: Entity, component: T) -> EcsResult<Option<T>> {
+        if let Some(dense_index) = self.dense_index(entity.id) {
+            let (chunk_index, inland_index) = self.split_index(dense_index);
+            self.entities[dense_index] = entity;
+
+            let slot = self.chunks[chunk_index].get_mut(inland_index)
+                .expect("Dense index points to an empty slot");
+            return Ok(Some(std::mem::replace(slot, component)));
+        }
+
+        let dense_index = self.entities.len();
+        let (chunk_index, _) = self.split_index(dense_index);
+
+        // The last chunk is full, take a new one from the allocator
+        if chunk_index == self.chunks.len() {
+            let pool = PoolTag::SparseSet { component: T::component_id() };
+            self.chunks.push(Chunk::try_new_in_pool(&self.allocator, self.capacity_per_chunk, pool)?);
+        }
+
+        self.chunks[chunk_index].try_add(component)?;
+        self.entities.push(entity);
+
+        let sparse_index = entity.id as usize;
+        if sparse_index >= self.sparse.len() {
+            self.sparse.resize(sparse_index + 1, EMPTY_SLOT);
+        }
+        self.sparse[sparse_index] = dense_index as u32;
+
+        Ok(None)
+    }
+
+    /// Removes the entity's component, moving the last component into its slot
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let dense_index = self.dense_index(id)?;
+        let last_index = self.entities.len() - 1;
+
+        // The last component always sits at the end of the last chunk
+        let (last_chunk, last_inland) = self.split_index(last_index);
+        let last = self.chunks[last_chunk].swap_take(last_inland)?;
+
+        self.sparse[id as usize] = EMPTY_SLOT;
+        self.entities.swap_remove(dense_index);
+        self.release_empty_chunks();
+
+        if dense_index == last_index {
+            return Some(last);
+        }
+
+        // Put the last component into the freed slot and fix its sparse entry
+        let (chunk_index, inland_index) = self.split_index(dense_index);
+        let slot = self.chunks[chunk_index].get_mut(inland_index)?;
+        let removed = std::mem::replace(slot, last);
+
+        let moved = self.entities[dense_index];
+        self.sparse[moved.id as usize] = dense_index as u32;
+
+        Some(removed)
+    }
+
+    #[inline]
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.dense_index(id).is_some()
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        let (chunk_index, inland_index) = self.split_index(self.dense_index(id)?);
+        self.chunks[chunk_index].get(inland_index)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        let (chunk_index, inland_index) = self.split_index(self.dense_index(id)?);
+        self.chunks[chunk_index].get_mut(inland_index)
+    }
+
+    /// Gets a raw pointer to the entity's component
+    ///
+    /// The pointer points into chunk memory, so writing through it is allowed
+    /// as long as no reference to the same component is alive
+    pub fn get_ptr(&self, id: EntityId) -> Option<NonNull<T>> {
+        let (chunk_index, inland_index) = self.split_index(self.dense_index(id)?);
+        self.chunks[chunk_index].get_ptr(inland_index)
+    }
+
+    /// Entities owning a component, in dense order
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Number of chunks currently held, including the empty ones kept for reuse
+    pub fn chunks_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Gives trailing empty chunks back to the allocator, keeping one for the next inserts
+    ///
+    /// Components are always packed at the front, so only the last chunks can be empty.
+    fn release_empty_chunks(&mut self) {
+        let used_chunks = self.entities.len().div_ceil(self.capacity_per_chunk);
+        while self.chunks.len() > used_chunks + 1
+            && (self.chunks.len() - used_chunks) as f32 > self.chunks.len() as f32 * MAX_EMPTY_CHUNKS_RATIO
+        {
+            self.chunks.pop();
+        }
+    }
+}
+
+/// Type-erased view of a sparse set
+pub trait SparseStorage {
+    /// ID of the stored component type
+    fn component_id(&self) -> ComponentId;
+
+    fn contains(&self, id: EntityId) -> bool;
+
+    /// Removes and drops the entity's component
+    fn remove(&mut self, id: EntityId) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Points the chunks moved by an arena compaction to their new memory
+    fn relocate(&mut self, relocations: &Relocations);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> SparseStorage for SparseSet<T> {
+    fn component_id(&self) -> ComponentId {
+        T::component_id()
+    }
+
+    fn contains(&self, id: EntityId) -> bool {
+        SparseSet::contains(self, id)
+    }
+
+    fn remove(&mut self, id: EntityId) -> bool {
+        SparseSet::remove(self, id).is_some()
+    }
+
+    fn relocate(&mut self, relocations: &Relocations) {
+        for chunk in &mut self.chunks {
+            chunk.relocate(relocations);
+        }
+    }
+
+    fn len(&self) -> usize {
+        SparseSet::len(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
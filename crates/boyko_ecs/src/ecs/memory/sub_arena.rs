@@ -0,0 +1,75 @@
+use std::alloc::Layout;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use crate::ecs::constants::CACHE_LINE_SIZE;
+use crate::ecs::error::{EcsError, EcsResult};
+use crate::ecs::memory::arena::Arena;
+use crate::ecs::memory::utils::align_up;
+
+/// Owner tag of sub-arena blocks in the parent's stats
+const SUB_ARENA_OWNER: &str = "SubArena";
+
+/// Child arena living in one block of a parent arena
+///
+/// Derefs to `Arena`, so it is used like any other arena, sub-arenas of its own
+/// included. It never grows beyond its block and has no scratch region. Dropping it
+/// gives the block back to the parent in one step, whatever is still allocated in it,
+/// which makes it a fit for data that lives exactly as long as a level or a scene.
+/// The lifetime ties it to the parent, and everything allocated from it is tied to it.
+pub struct SubArena<'a> {
+    arena: ManuallyDrop<Arena>,
+
+    parent: &'a Arena,
+
+    /// Block of the parent that backs the arena
+    block: NonNull<u8>,
+
+    layout: Layout,
+}
+
+// The block is only reached through the inner arena, which is Send and Sync itself
+unsafe impl Send for SubArena<'_> {}
+unsafe impl Sync for SubArena<'_> {}
+
+impl<'a> SubArena<'a> {
+    #[track_caller]
+    pub(crate) fn new(parent: &'a Arena, size: usize) -> EcsResult<Self> {
+        if size == 0 {
+            return Err(EcsError::InvalidLayout);
+        }
+
+        let layout = Layout::from_size_align(align_up(size, CACHE_LINE_SIZE), CACHE_LINE_SIZE)?;
+        let block = parent.try_allocate_owned(layout, SUB_ARENA_OWNER)?;
+        let arena = unsafe { Arena::in_parent_block(block, layout.size()) };
+
+        Ok(Self { arena: ManuallyDrop::new(arena), parent, block, layout })
+    }
+
+    #[inline]
+    pub fn parent(&self) -> &'a Arena {
+        self.parent
+    }
+}
+
+impl Deref for SubArena<'_> {
+    type Target = Arena;
+
+    #[inline]
+    fn deref(&self) -> &Arena {
+        &self.arena
+    }
+}
+
+impl Drop for SubArena<'_> {
+    fn drop(&mut self) {
+        // Releasing the allocations in bulk is the point, so they are not reported as leaks
+        self.arena.discard_allocations();
+
+        unsafe {
+            ManuallyDrop::drop(&mut self.arena);
+        }
+
+        self.parent.deallocate_owned(self.block, self.layout, SUB_ARENA_OWNER);
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use crate::ecs::constants::{MIN_BLOCK_ALIGN, TLSF_SECOND_LEVEL_LOG2};
+use crate::ecs::memory::free_mem_block::{FreeBlockPolicy, MemFreeBlock};
+use crate::ecs::memory::utils::align_up;
+
+/// Number of lists per first-level class
+const SECOND_LEVEL_COUNT: usize = 1 << TLSF_SECOND_LEVEL_LOG2;
+
+/// Sizes below this share the first first-level class, one exact size per list
+const SMALL_BLOCK_SIZE: usize = SECOND_LEVEL_COUNT;
+
+/// One class for the small sizes plus one per power of two from `SMALL_BLOCK_SIZE` up
+const FIRST_LEVEL_COUNT: usize = usize::BITS as usize - TLSF_SECOND_LEVEL_LOG2 + 1;
+
+/// End of a free list
+const NIL: usize = usize::MAX;
+
+/// Free block linked into the list of its size class
+#[derive(Clone, Copy)]
+struct TlsfNode {
+    block: MemFreeBlock,
+
+    prev: usize,
+
+    next: usize,
+}
+
+/// Two-Level Segregated Fit free block tracking
+///
+/// Free blocks are kept in lists by size class: the first level splits sizes by
+/// powers of two, the second level splits every power of two into
+/// `2^TLSF_SECOND_LEVEL_LOG2` linear steps. Two bitmaps mark the non-empty lists,
+/// so finding a fitting list takes a couple of bit scans and allocation and free
+/// never search. Block metadata lives outside the region, because mmap regions
+/// are not committed yet when their free space is tracked.
+///
+/// Block offsets and sizes are expected to be multiples of `MIN_BLOCK_ALIGN`,
+/// which the arena regions guarantee by handing out whole granules.
+pub struct TlsfFreeBlocks {
+    nodes: Vec<TlsfNode>,
+
+    /// Unused entries of `nodes`
+    free_nodes: Vec<usize>,
+
+    /// Bit per first-level class with a non-empty list
+    first_level: usize,
+
+    /// Bit per non-empty list of every first-level class
+    second_level: [usize; FIRST_LEVEL_COUNT],
+
+    /// First node of every list, `NIL` for empty ones
+    heads: Vec<usize>,
+
+    /// Blocks by start offset, for merging with the block before them
+    start_map: HashMap<usize, usize>,
+
+    /// Blocks by end offset, for merging with the block after them
+    end_map: HashMap<usize, usize>,
+
+    free_size: usize,
+}
+
+impl TlsfFreeBlocks {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            first_level: 0,
+            second_level: [0; FIRST_LEVEL_COUNT],
+            heads: vec![NIL; FIRST_LEVEL_COUNT * SECOND_LEVEL_COUNT],
+            start_map: HashMap::new(),
+            end_map: HashMap::new(),
+            free_size: 0,
+        }
+    }
+
+    pub fn new_init(arena_size: usize) -> Self {
+        let mut free_blocks = Self::new();
+        free_blocks.insert(MemFreeBlock::new(0, arena_size));
+        free_blocks
+    }
+
+    /// Adds a block, merging it with the free blocks around it
+    pub fn insert(&mut self, mut block: MemFreeBlock) {
+        debug_assert!(block.size() != 0);
+
+        if let Some(&left) = self.end_map.get(&block.start) {
+            block.start = self.remove(left).start;
+        }
+
+        if let Some(&right) = self.start_map.get(&block.end) {
+            block.end = self.remove(right).end;
+        }
+
+        self.link(block);
+    }
+
+    /// Takes a block of `size` bytes at an address aligned to `align`, `base` is the address of offset 0
+    pub fn allocate_aligned(&mut self, size: usize, align: usize, base: usize) -> Option<MemFreeBlock> {
+        if size == 0 {
+            return None;
+        }
+        debug_assert!(base.is_multiple_of(MIN_BLOCK_ALIGN) && size.is_multiple_of(MIN_BLOCK_ALIGN));
+
+        let index = self.find_aligned(size, align, base)?;
+        let block = self.remove(index);
+
+        let start = align_up(base + block.start, align) - base;
+        let end = start + size;
+
+        // The space skipped for the alignment goes back to the free lists
+        if start > block.start {
+            self.link(MemFreeBlock::new(block.start, start));
+        }
+
+        if block.end > end {
+            self.link(MemFreeBlock::new(end, block.end));
+        }
+
+        Some(MemFreeBlock::new(start, end))
+    }
+
+    /// Takes `size` bytes from the front of the free block that starts at `start`
+    pub fn take_front(&mut self, start: usize, size: usize) -> bool {
+        let Some(&index) = self.start_map.get(&start) else {
+            return false;
+        };
+
+        if self.nodes[index].block.size() < size {
+            return false;
+        }
+
+        let block = self.remove(index);
+        if block.size() > size {
+            self.link(MemFreeBlock::new(start + size, block.end));
+        }
+
+        true
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.start_map.len()
+    }
+
+    pub fn total_free_size(&self) -> usize {
+        self.free_size
+    }
+
+    /// Size of the largest request the free lists are guaranteed to serve, 0 if there is none
+    ///
+    /// Read from the bitmaps: the lower bound of the highest non-empty class, which is
+    /// at most one second-level step below the size of the largest block.
+    pub fn largest_free_block(&self) -> usize {
+        if self.first_level == 0 {
+            return 0;
+        }
+
+        let first = self.first_level.ilog2() as usize;
+        let second = self.second_level[first].ilog2() as usize;
+        Self::class_size(first, second)
+    }
+
+    /// Finds a block that holds `size` bytes at `align`
+    ///
+    /// Block starts are multiples of `MIN_BLOCK_ALIGN`, so a larger alignment skips at most
+    /// `align - MIN_BLOCK_ALIGN` bytes. The head of the best fitting list is taken if it
+    /// happens to be aligned well enough, otherwise the search asks for that much more,
+    /// which makes every block of the found list fit. The skipped space is split off by the caller.
+    fn find_aligned(&self, size: usize, align: usize, base: usize) -> Option<usize> {
+        let (first, second) = Self::search_mapping(size)?;
+        let (first, second) = self.find_suitable(first, second)?;
+        let index = self.heads[Self::slot(first, second)];
+        if align <= MIN_BLOCK_ALIGN {
+            return Some(index);
+        }
+
+        let block = self.nodes[index].block;
+        if align_up(base + block.start, align) - base + size <= block.end {
+            return Some(index);
+        }
+
+        let (first, second) = Self::search_mapping(size.checked_add(align - MIN_BLOCK_ALIGN)?)?;
+        let (first, second) = self.find_suitable(first, second)?;
+        Some(self.heads[Self::slot(first, second)])
+    }
+
+    /// First non-empty list at or above the class
+    fn find_suitable(&self, first: usize, second: usize) -> Option<(usize, usize)> {
+        let mut first = first;
+        let mut second_map = self.second_level[first] & (usize::MAX << second);
+
+        if second_map == 0 {
+            let first_map = self.first_level & (usize::MAX << (first + 1));
+            if first_map == 0 {
+                return None;
+            }
+
+            first = first_map.trailing_zeros() as usize;
+            second_map = self.second_level[first];
+        }
+
+        Some((first, second_map.trailing_zeros() as usize))
+    }
+
+    /// Class holding blocks of exactly `size` bytes
+    fn mapping(size: usize) -> (usize, usize) {
+        if size < SMALL_BLOCK_SIZE {
+            return (0, size);
+        }
+
+        let log2 = size.ilog2() as usize;
+        let second = (size >> (log2 - TLSF_SECOND_LEVEL_LOG2)) - SECOND_LEVEL_COUNT;
+        (log2 - TLSF_SECOND_LEVEL_LOG2 + 1, second)
+    }
+
+    /// Smallest block size of the class, the inverse of `mapping`
+    fn class_size(first: usize, second: usize) -> usize {
+        if first == 0 {
+            return second;
+        }
+
+        let log2 = first + TLSF_SECOND_LEVEL_LOG2 - 1;
+        (SECOND_LEVEL_COUNT + second) << (log2 - TLSF_SECOND_LEVEL_LOG2)
+    }
+
+    /// Lowest class whose blocks all hold at least `size` bytes
+    fn search_mapping(size: usize) -> Option<(usize, usize)> {
+        if size < SMALL_BLOCK_SIZE {
+            return Some(Self::mapping(size));
+        }
+
+        let step = 1 << (size.ilog2() as usize - TLSF_SECOND_LEVEL_LOG2);
+        Some(Self::mapping(size.checked_add(step - 1)?))
+    }
+
+    #[inline(always)]
+    fn slot(first: usize, second: usize) -> usize {
+        first * SECOND_LEVEL_COUNT + second
+    }
+
+    /// Pushes the block to the front of its list without merging
+    fn link(&mut self, block: MemFreeBlock) {
+        let (first, second) = Self::mapping(block.size());
+        let slot = Self::slot(first, second);
+        let head = self.heads[slot];
+
+        let node = TlsfNode { block, prev: NIL, next: head };
+        let index = match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        if head != NIL {
+            self.nodes[head].prev = index;
+        }
+
+        self.heads[slot] = index;
+        self.first_level |= 1 << first;
+        self.second_level[first] |= 1 << second;
+
+        self.start_map.insert(block.start, index);
+        self.end_map.insert(block.end, index);
+        self.free_size += block.size();
+    }
+
+    /// Unlinks the block from its list
+    fn remove(&mut self, index: usize) -> MemFreeBlock {
+        let TlsfNode { block, prev, next } = self.nodes[index];
+
+        if prev != NIL {
+            self.nodes[prev].next = next;
+        }
+
+        if next != NIL {
+            self.nodes[next].prev = prev;
+        }
+
+        let (first, second) = Self::mapping(block.size());
+        let slot = Self::slot(first, second);
+
+        if self.heads[slot] == index {
+            self.heads[slot] = next;
+
+            if next == NIL {
+                self.second_level[first] &= !(1 << second);
+                if self.second_level[first] == 0 {
+                    self.first_level &= !(1 << first);
+                }
+            }
+        }
+
+        self.start_map.remove(&block.start);
+        self.end_map.remove(&block.end);
+        self.free_nodes.push(index);
+        self.free_size -= block.size();
+
+        block
+    }
+}
+
+impl FreeBlockPolicy for TlsfFreeBlocks {
+    fn insert(&mut self, block: MemFreeBlock) {
+        TlsfFreeBlocks::insert(self, block);
+    }
+
+    fn allocate_aligned(&mut self, size: usize, align: usize, base: usize) -> Option<MemFreeBlock> {
+        TlsfFreeBlocks::allocate_aligned(self, size, align, base)
+    }
+
+    fn take_front(&mut self, start: usize, size: usize) -> bool {
+        TlsfFreeBlocks::take_front(self, start, size)
+    }
+
+    fn block_count(&self) -> usize {
+        TlsfFreeBlocks::block_count(self)
+    }
+
+    fn total_free_size(&self) -> usize {
+        TlsfFreeBlocks::total_free_size(self)
+    }
+
+    fn largest_free_block(&self) -> usize {
+        TlsfFreeBlocks::largest_free_block(self)
+    }
+
+    fn blocks(&self) -> Vec<MemFreeBlock> {
+        self.start_map.values().map(|&index| self.nodes[index].block).collect()
+    }
+}
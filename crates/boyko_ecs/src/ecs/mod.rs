@@ -1,3 +1,4 @@
 pub mod core;
 pub mod memory;
-pub mod constants;
\ No newline at end of file
+pub mod constants;
+pub mod error;
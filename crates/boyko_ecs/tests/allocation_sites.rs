@@ -0,0 +1,143 @@
+//! Leak reports name the code that created or grew a storage, not the pool internals,
+//! and tell which storage a chunk belongs to.
+//!
+//! Allocations are only tracked in debug builds or with the `debug-arena` feature.
+#![cfg(any(debug_assertions, feature = "debug-arena"))]
+
+use std::panic::Location;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use boyko_ecs::ecs::core::archetype::Archetype;
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId, ComponentInfo, StorageType};
+use boyko_ecs::ecs::core::ecs_master::EcsMaster;
+use boyko_ecs::ecs::core::entity::Entity;
+use boyko_ecs::ecs::memory::allocation_tracker::{AllocationRecord, LeakReport, PoolTag};
+use boyko_ecs::ecs::memory::arena::{Arena, ArenaConfig};
+use boyko_ecs::ecs::memory::chunk_allocator::ChunkAllocator;
+use boyko_ecs::ecs::memory::component_pool::ComponentPool;
+
+struct Position(#[allow(dead_code)] [f32; 3]);
+
+impl Component for Position {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+struct Tag(#[allow(dead_code)] u64);
+
+impl Component for Tag {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+
+    fn storage_type() -> StorageType {
+        StorageType::Sparse
+    }
+}
+
+fn arena() -> (Arc<Arena>, Arc<dyn ChunkAllocator>) {
+    let arena = Arc::new(Arena::new());
+    let allocator: Arc<dyn ChunkAllocator> = arena.clone();
+    (arena, allocator)
+}
+
+/// Live chunk records of the pool
+fn chunks_of(arena: &Arena, pool: PoolTag) -> Vec<AllocationRecord> {
+    arena.leak_report().allocations.into_iter()
+        .filter(|record| record.pool == Some(pool))
+        .collect()
+}
+
+/// Every record was allocated on the same line as `location`
+fn assert_allocated_at(records: &[AllocationRecord], location: &Location<'static>) {
+    assert!(!records.is_empty(), "no chunks were recorded");
+    for record in records {
+        assert_eq!(record.location.file(), location.file(), "{record}");
+        assert_eq!(record.location.line(), location.line(), "{record}");
+    }
+}
+
+#[test]
+fn pool_chunks_point_at_the_pool_creation() {
+    let (arena, allocator) = arena();
+
+    let (pool, created_at) = (ComponentPool::<Position>::new(&allocator, 3, 16), Location::caller());
+
+    let records = chunks_of(&arena, PoolTag::Standalone);
+    assert_eq!(records.len(), 3);
+    assert_allocated_at(&records, created_at);
+    assert_eq!(records[0].owner, Some(Position::name()));
+
+    // Until the pool is dropped the report lists its chunks at the same site
+    assert!(arena.leak_report().to_string().contains(file!()));
+    drop(pool);
+    assert!(arena.leak_report().is_empty());
+}
+
+#[test]
+fn grown_chunks_point_at_the_add() {
+    let (arena, allocator) = arena();
+    let mut pool = ComponentPool::<Position>::new(&allocator, 0, 4);
+
+    let (_, added_at) = (pool.add(Position([0.0; 3])), Location::caller());
+
+    assert_allocated_at(&chunks_of(&arena, PoolTag::Standalone), added_at);
+}
+
+#[test]
+fn archetype_columns_are_tagged() {
+    let (arena, allocator) = arena();
+    let infos = [ComponentInfo::of::<Position>()];
+
+    let (archetype, created_at) = (Archetype::new(7, &allocator, &infos), Location::caller());
+
+    let entities = chunks_of(&arena, PoolTag::Entities { archetype: 7 });
+    let column = chunks_of(&arena, PoolTag::Column { archetype: 7, component: Position::component_id() });
+    assert_allocated_at(&entities, created_at);
+    assert_allocated_at(&column, created_at);
+    assert_eq!(column[0].owner, Some(Position::name()));
+    assert_eq!(entities[0].owner, Some(Entity::name()));
+
+    drop(archetype);
+    assert!(arena.leak_report().is_empty());
+}
+
+#[test]
+fn world_storages_point_at_the_world_calls() {
+    let (mut world, created_at) = (EcsMaster::new(), Location::caller());
+    assert_allocated_at(&chunks_of(world.arena(), PoolTag::Entities { archetype: 0 }), created_at);
+
+    let entity = world.spawn();
+    let (_, inserted_at) = (world.insert(entity, Position([1.0; 3])), Location::caller());
+    let archetype = world.location(entity).unwrap().archetype_id;
+
+    let column = PoolTag::Column { archetype, component: Position::component_id() };
+    assert_allocated_at(&chunks_of(world.arena(), column), inserted_at);
+    assert_allocated_at(&chunks_of(world.arena(), PoolTag::Entities { archetype }), inserted_at);
+
+    let (_, sparse_at) = (world.insert(entity, Tag(1)), Location::caller());
+    let sparse = PoolTag::SparseSet { component: Tag::component_id() };
+    assert_allocated_at(&chunks_of(world.arena(), sparse), sparse_at);
+}
+
+#[test]
+fn leak_hook_gets_the_report_on_drop() {
+    static LEAKED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_leaks(report: &LeakReport) {
+        LEAKED_BYTES.fetch_add(report.total_bytes(), Ordering::Relaxed);
+    }
+
+    let arena = Arena::with_config(ArenaConfig { leak_hook: Some(count_leaks), ..ArenaConfig::default() });
+    let layout = std::alloc::Layout::new::<[u64; 4]>();
+    arena.allocate_layout(layout);
+    drop(arena);
+    assert_eq!(LEAKED_BYTES.load(Ordering::Relaxed), layout.size());
+
+    // Without a hook leaks are only available through `leak_report`
+    let arena = Arena::new();
+    arena.allocate_layout(layout);
+    drop(arena);
+    assert_eq!(LEAKED_BYTES.load(Ordering::Relaxed), layout.size());
+}
@@ -0,0 +1,77 @@
+//! `allocator_api2` collections backed by an arena: growth and shrinking keep the
+//! contents, and blocks are resized in place when the space after them is free.
+#![cfg(feature = "allocator-api2")]
+
+use allocator_api2::vec::Vec;
+use boyko_ecs::ecs::memory::arena::Arena;
+
+/// Single-region arena, so blocks are placed one after another
+fn fixed_arena() -> Arena {
+    Arena::with_limit(1024 * 1024, 1024 * 1024)
+}
+
+#[test]
+fn vec_grows_and_shrinks_in_the_arena() {
+    let arena = fixed_arena();
+    let mut values = Vec::new_in(&arena);
+
+    for value in 0..10_000u64 {
+        values.push(value);
+    }
+    assert!(values.iter().copied().eq(0..10_000));
+    assert!(arena.stats().used_bytes >= 10_000 * 8);
+
+    values.truncate(100);
+    values.shrink_to_fit();
+    assert_eq!(values.capacity(), 100);
+    assert!(values.iter().copied().eq(0..100));
+    assert_eq!(arena.stats().used_bytes, 100 * 8);
+
+    drop(values);
+    assert_eq!(arena.stats().used_bytes, 0);
+}
+
+#[test]
+fn vecs_of_every_size_class_keep_their_contents() {
+    let arena = fixed_arena();
+    let mut vecs: std::vec::Vec<Vec<u32, &Arena>> = (0..8).map(|_| Vec::new_in(&arena)).collect();
+
+    // Interleaved growth, so no vec can always grow in place
+    for round in 0..2000u32 {
+        for (index, vec) in vecs.iter_mut().enumerate() {
+            vec.push(round * 8 + index as u32);
+        }
+    }
+
+    for (index, vec) in vecs.iter().enumerate() {
+        assert!(vec.iter().copied().eq((0..2000).map(|round| round * 8 + index as u32)));
+    }
+}
+
+// The canaries of the `debug-arena` feature sit right behind a block, so it never resizes in place
+#[cfg(not(feature = "debug-arena"))]
+#[test]
+fn grow_stays_in_place_while_the_next_block_is_free() {
+    let arena = fixed_arena();
+
+    // Above the thread cache classes, so the block sits in front of the free tail
+    let mut values: Vec<u64, &Arena> = Vec::with_capacity_in(1024, &arena);
+    values.extend(0..1024);
+    let ptr = values.as_ptr();
+
+    values.reserve_exact(2048);
+    assert_eq!(values.as_ptr(), ptr);
+    assert!(values.capacity() >= 3072);
+
+    // Shrinking gives the tail back without moving either
+    values.shrink_to_fit();
+    assert_eq!(values.as_ptr(), ptr);
+    assert!(values.iter().copied().eq(0..1024));
+
+    // Once the next block is taken, growing has to move
+    let blocker: Vec<u8, &Arena> = Vec::with_capacity_in(8192, &arena);
+    values.reserve_exact(4096);
+    assert_ne!(values.as_ptr(), ptr);
+    assert!(values.iter().copied().eq(0..1024));
+    drop(blocker);
+}
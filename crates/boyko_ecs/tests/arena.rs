@@ -0,0 +1,134 @@
+//! Arena allocation and deallocation: freed blocks merge with their free neighbours
+//! and are handed out again, storages give all their memory back when dropped, and
+//! a full arena adds regions up to its limit without moving what it handed out.
+
+use std::alloc::Layout;
+use std::sync::Arc;
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::error::EcsError;
+use boyko_ecs::ecs::memory::arena::Arena;
+use boyko_ecs::ecs::memory::chunk::Chunk;
+use boyko_ecs::ecs::memory::chunk_allocator::ChunkAllocator;
+use boyko_ecs::ecs::memory::component_pool::ComponentPool;
+
+/// Blocks above the largest thread cache class, so they go straight to the free blocks
+const BLOCK: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Position([f32; 3]);
+
+impl Component for Position {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+fn block(size: usize) -> Layout {
+    Layout::from_size_align(size, 8).unwrap()
+}
+
+/// Arena that never grows past its first region
+fn fixed_arena(capacity: usize) -> Arena {
+    Arena::with_limit(capacity, capacity)
+}
+
+#[test]
+fn freed_neighbours_coalesce_into_one_block() {
+    let arena = fixed_arena(16 * BLOCK);
+    let blocks: Vec<_> = (0..3).map(|_| arena.allocate_layout(block(BLOCK))).collect();
+
+    arena.deallocate(blocks[0], block(BLOCK));
+    arena.deallocate(blocks[1], block(BLOCK));
+
+    // Only the merged range holds both blocks without touching the third one
+    let spanning = arena.allocate_layout(block(2 * BLOCK));
+    assert_eq!(spanning, blocks[0]);
+
+    unsafe { spanning.as_ptr().write_bytes(0xAB, 2 * BLOCK) };
+    arena.deallocate(spanning, block(2 * BLOCK));
+    arena.deallocate(blocks[2], block(BLOCK));
+
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.free_block_count, 1);
+}
+
+#[test]
+fn freed_blocks_are_reused() {
+    let arena = fixed_arena(4 * BLOCK);
+
+    // Four times the capacity goes through the arena without it growing
+    for _ in 0..16 {
+        let ptr = arena.allocate_layout(block(BLOCK));
+        arena.deallocate(ptr, block(BLOCK));
+    }
+
+    assert_eq!(arena.region_count(), 1);
+    assert_eq!(arena.stats().used_bytes, 0);
+}
+
+#[test]
+fn chunks_return_their_memory_on_drop() {
+    let arena = Arc::new(Arena::new());
+    let allocator: Arc<dyn ChunkAllocator> = arena.clone();
+
+    let mut chunk = Chunk::<Position>::new(&allocator, 1024);
+    chunk.add(Position([1.0; 3]));
+    assert!(arena.stats().used_bytes >= 1024 * size_of::<Position>());
+
+    drop(chunk);
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.allocation_count, 0);
+}
+
+#[test]
+fn pools_return_their_memory_on_drop() {
+    let arena = Arc::new(Arena::new());
+    let allocator: Arc<dyn ChunkAllocator> = arena.clone();
+
+    let mut pool = ComponentPool::<Position>::new(&allocator, 4, 256);
+    for index in 0..2000 {
+        pool.add(Position([index as f32; 3]));
+    }
+    assert!(arena.stats().allocation_count > 4);
+
+    drop(pool);
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.allocation_count, 0);
+}
+
+#[test]
+fn full_arena_grows_until_its_limit() {
+    let arena = Arena::with_limit(4 * BLOCK, 16 * BLOCK);
+    let mut blocks = Vec::new();
+
+    let error = loop {
+        match arena.try_allocate_layout(block(BLOCK)) {
+            Ok(ptr) => {
+                unsafe { ptr.as_ptr().write_bytes(blocks.len() as u8, BLOCK) };
+                blocks.push(ptr);
+            }
+            Err(error) => break error,
+        }
+    };
+
+    assert!(matches!(error, EcsError::OutOfMemory { .. }), "{error}");
+    assert!(arena.region_count() > 1);
+    assert!(blocks.len() > 4);
+    assert!(arena.capacity() <= arena.max_capacity());
+
+    // New regions are separate allocations, the blocks in the old ones never move
+    for (index, ptr) in blocks.iter().enumerate() {
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), BLOCK) };
+        assert!(bytes.iter().all(|&byte| byte == index as u8), "block {index} was overwritten");
+    }
+
+    // The limit only stops growth, freed space is still handed out
+    let regions = arena.region_count();
+    let last = blocks.pop().unwrap();
+    arena.deallocate(last, block(BLOCK));
+    assert_eq!(arena.try_allocate_layout(block(BLOCK)).ok(), Some(last));
+    assert_eq!(arena.region_count(), regions);
+}
@@ -0,0 +1,148 @@
+//! Arena collections: boxes and vectors free their memory back into the arena and
+//! drop every element exactly once, and interned strings are stored once each.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use boyko_ecs::ecs::memory::arena::Arena;
+use boyko_ecs::ecs::memory::arena_box::ArenaBox;
+use boyko_ecs::ecs::memory::arena_str::StrInterner;
+use boyko_ecs::ecs::memory::arena_vec::ArenaVec;
+
+/// Counts its drops in a shared counter
+struct DropCounter<'c>(&'c Cell<usize>);
+
+impl Drop for DropCounter<'_> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn box_frees_its_value_on_drop() {
+    let arena = Arena::new();
+    let drops = Cell::new(0);
+
+    let boxed = ArenaBox::new_in((DropCounter(&drops), [7u64; 64]), &arena);
+    assert_eq!(boxed.1, [7; 64]);
+    assert!(arena.stats().used_bytes >= 64 * 8);
+
+    drop(boxed);
+    assert_eq!(drops.get(), 1);
+    assert_eq!(arena.stats().used_bytes, 0);
+    assert_eq!(arena.stats().allocation_count, 0);
+}
+
+#[test]
+fn box_into_inner_frees_without_dropping() {
+    let arena = Arena::new();
+    let drops = Cell::new(0);
+
+    let value = ArenaBox::new_in(DropCounter(&drops), &arena).into_inner();
+    assert_eq!(drops.get(), 0);
+    assert_eq!(arena.stats().used_bytes, 0);
+
+    drop(value);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn vec_keeps_its_contents_while_growing() {
+    let arena = Arena::new();
+    let mut values = ArenaVec::new_in(&arena);
+    assert_eq!(values.capacity(), 0);
+
+    let mut capacities = Vec::new();
+    for value in 0..10_000u64 {
+        values.push(value);
+        if capacities.last() != Some(&values.capacity()) {
+            capacities.push(values.capacity());
+        }
+    }
+
+    assert!(values.iter().copied().eq(0..10_000));
+    // Each step grows by half, so pushes stay amortized constant
+    assert_eq!(capacities[0], 4);
+    assert!(capacities.windows(2).all(|pair| pair[1] == pair[0] * 3 / 2), "{capacities:?}");
+
+    values.truncate(10);
+    values.shrink_to_fit();
+    assert_eq!(values.capacity(), 10);
+    assert!(values.iter().copied().eq(0..10));
+
+    drop(values);
+    assert_eq!(arena.stats().used_bytes, 0);
+}
+
+#[test]
+fn vec_drops_every_element_once() {
+    let arena = Arena::new();
+    let drops = Cell::new(0);
+    let mut values = ArenaVec::new_in(&arena);
+
+    for _ in 0..100 {
+        values.push(DropCounter(&drops));
+    }
+
+    drop(values.pop());
+    drop(values.swap_remove(0));
+    assert_eq!(drops.get(), 2);
+
+    values.truncate(50);
+    assert_eq!(drops.get(), 50);
+
+    drop(values);
+    assert_eq!(drops.get(), 100);
+    assert_eq!(arena.stats().used_bytes, 0);
+}
+
+#[test]
+fn zero_sized_elements_take_no_memory() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Zero-sized, but still with drop glue
+    #[derive(Debug, PartialEq)]
+    struct Marker;
+
+    impl Drop for Marker {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let arena = Arena::new();
+    let mut markers = ArenaVec::new_in(&arena);
+    for _ in 0..1000 {
+        markers.push(Marker);
+    }
+    assert_eq!(markers.len(), 1000);
+
+    let boxed = ArenaBox::new_in(Marker, &arena);
+    assert_eq!(arena.stats().allocation_count, 0);
+
+    drop(boxed);
+    drop(markers);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1001);
+}
+
+#[test]
+fn equal_strings_are_interned_once() {
+    let arena = Arena::new();
+    let interner = StrInterner::new(&arena);
+
+    let first = interner.intern("position");
+    let owned = String::from("position");
+    let second = interner.intern(&owned);
+    let other = interner.intern("velocity");
+
+    // The same string comes back as the same bytes, not just equal ones
+    assert_eq!(first.as_ptr(), second.as_ptr());
+    assert_ne!(first.as_ptr(), owned.as_ptr());
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+    assert_eq!(interner.get("position").map(|value| value.as_ptr()), Some(first.as_ptr()));
+    assert_eq!(interner.get("rotation"), None);
+    assert_eq!(interner.len(), 2);
+
+    drop(interner);
+    assert_eq!(arena.stats().used_bytes, 0);
+}
@@ -0,0 +1,146 @@
+//! Arena stats: used and free bytes, the largest free block, fragmentation, the
+//! high-water mark and the per-owner breakdown follow known allocations exactly.
+// The canaries of the `debug-arena` feature make every block larger than requested
+#![cfg(not(feature = "debug-arena"))]
+
+use std::alloc::Layout;
+use boyko_ecs::ecs::memory::arena::{Arena, ArenaConfig, FreeBlockStrategy};
+use boyko_ecs::ecs::memory::arena_stats::OwnerStats;
+
+/// Blocks above the largest thread cache class, so they go straight to the free blocks
+const BLOCK: usize = 8 * 1024;
+
+const CAPACITY: usize = 16 * BLOCK;
+
+fn block(size: usize) -> Layout {
+    Layout::from_size_align(size, 8).unwrap()
+}
+
+fn fixed_arena(free_blocks: FreeBlockStrategy) -> Arena {
+    Arena::with_config(ArenaConfig {
+        capacity: CAPACITY,
+        max_capacity: CAPACITY,
+        free_blocks,
+        ..ArenaConfig::default()
+    })
+}
+
+#[test]
+fn fresh_arena_is_one_free_block() {
+    let stats = fixed_arena(FreeBlockStrategy::BestFit).stats();
+
+    assert_eq!(stats.capacity, CAPACITY);
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.free_bytes, CAPACITY);
+    assert_eq!(stats.largest_free_block, CAPACITY);
+    assert_eq!(stats.free_block_count, 1);
+    assert_eq!(stats.fragmentation, 0.0);
+    assert_eq!(stats.high_water_mark, 0);
+    assert!(stats.owners.is_empty());
+}
+
+#[test]
+fn holes_split_the_free_space() {
+    let arena = fixed_arena(FreeBlockStrategy::BestFit);
+    let blocks: Vec<_> = (0..4).map(|_| arena.allocate_layout(block(BLOCK))).collect();
+
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 4 * BLOCK);
+    assert_eq!(stats.free_bytes, 12 * BLOCK);
+    assert_eq!(stats.largest_free_block, 12 * BLOCK);
+    assert_eq!(stats.fragmentation, 0.0);
+    assert_eq!(stats.allocation_count, 4);
+
+    // A hole between live blocks cannot merge with the tail
+    arena.deallocate(blocks[1], block(BLOCK));
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 3 * BLOCK);
+    assert_eq!(stats.free_bytes, 13 * BLOCK);
+    assert_eq!(stats.largest_free_block, 12 * BLOCK);
+    assert_eq!(stats.free_block_count, 2);
+    assert_eq!(stats.fragmentation, 1.0 - 12.0 / 13.0);
+
+    // Freeing the neighbours joins everything again
+    for &ptr in [blocks[0], blocks[2], blocks[3]].iter() {
+        arena.deallocate(ptr, block(BLOCK));
+    }
+    let stats = arena.stats();
+    assert_eq!(stats.free_bytes, CAPACITY);
+    assert_eq!(stats.free_block_count, 1);
+    assert_eq!(stats.fragmentation, 0.0);
+    assert_eq!(stats.allocation_count, 0);
+    assert_eq!(stats.total_allocations, 4);
+}
+
+#[test]
+fn high_water_mark_keeps_the_peak() {
+    let arena = fixed_arena(FreeBlockStrategy::BestFit);
+
+    let first = arena.allocate_layout(block(3 * BLOCK));
+    let second = arena.allocate_layout(block(BLOCK));
+    arena.deallocate(first, block(3 * BLOCK));
+    assert_eq!(arena.stats().high_water_mark, 4 * BLOCK);
+
+    // Lower usage later does not move it, a new peak does
+    let third = arena.allocate_layout(block(2 * BLOCK));
+    assert_eq!(arena.stats().used_bytes, 3 * BLOCK);
+    assert_eq!(arena.stats().high_water_mark, 4 * BLOCK);
+
+    let fourth = arena.allocate_layout(block(2 * BLOCK));
+    assert_eq!(arena.stats().high_water_mark, 5 * BLOCK);
+
+    for (ptr, size) in [(second, BLOCK), (third, 2 * BLOCK), (fourth, 2 * BLOCK)] {
+        arena.deallocate(ptr, block(size));
+    }
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.high_water_mark, 5 * BLOCK);
+}
+
+#[test]
+fn owners_are_listed_largest_first() {
+    let arena = fixed_arena(FreeBlockStrategy::BestFit);
+    let positions: Vec<_> = (0..3)
+        .map(|_| arena.try_allocate_owned(block(BLOCK), "Position").unwrap())
+        .collect();
+    let velocity = arena.try_allocate_owned(block(4 * BLOCK), "Velocity").unwrap();
+    let untagged = arena.allocate_layout(block(BLOCK));
+
+    // Untagged memory counts as used but belongs to no owner
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 8 * BLOCK);
+    assert_eq!(stats.owners, vec![
+        OwnerStats { owner: "Velocity", bytes: 4 * BLOCK, allocations: 1 },
+        OwnerStats { owner: "Position", bytes: 3 * BLOCK, allocations: 3 },
+    ]);
+
+    // Owners without live allocations drop out of the list
+    arena.deallocate_owned(velocity, block(4 * BLOCK), "Velocity");
+    arena.deallocate_owned(positions[0], block(BLOCK), "Position");
+    assert_eq!(arena.stats().owners, vec![
+        OwnerStats { owner: "Position", bytes: 2 * BLOCK, allocations: 2 },
+    ]);
+
+    for &ptr in &positions[1..] {
+        arena.deallocate_owned(ptr, block(BLOCK), "Position");
+    }
+    arena.deallocate(untagged, block(BLOCK));
+    let stats = arena.stats();
+    assert!(stats.owners.is_empty());
+    assert_eq!(stats.used_bytes, 0);
+}
+
+#[test]
+fn tlsf_reports_a_largest_block_it_can_serve() {
+    let arena = fixed_arena(FreeBlockStrategy::Tlsf);
+    let blocks: Vec<_> = (0..4).map(|_| arena.allocate_layout(block(BLOCK))).collect();
+    arena.deallocate(blocks[1], block(BLOCK));
+
+    // TLSF rounds the largest block down to its size class, byte totals stay exact
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 3 * BLOCK);
+    assert_eq!(stats.free_bytes, 13 * BLOCK);
+    assert_eq!(stats.free_block_count, 2);
+    assert!(stats.largest_free_block > BLOCK && stats.largest_free_block <= 12 * BLOCK);
+    assert!(arena.try_allocate_layout(block(stats.largest_free_block)).is_ok());
+}
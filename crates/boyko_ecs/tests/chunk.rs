@@ -0,0 +1,391 @@
+//! Invariants of `Chunk<T>`: every slot below `count` is initialized, nothing above is,
+//! and every component is dropped exactly once, even when a destructor panics.
+//!
+//! Chunks use the system allocator or a small arena, so every test stays cheap enough
+//! to run under Miri: `scripts/miri.sh`.
+
+use std::cell::Cell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::Arc;
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::error::EcsError;
+use boyko_ecs::ecs::memory::arena::Arena;
+use boyko_ecs::ecs::memory::chunk::Chunk;
+use boyko_ecs::ecs::memory::chunk_allocator::{ChunkAllocator, SystemChunkAllocator};
+
+macro_rules! component {
+    ($($ty:ty),* $(,)?) => {
+        $(impl Component for $ty {
+            fn component_id() -> ComponentId {
+                dynamic_component_id::<Self>()
+            }
+        })*
+    };
+}
+
+/// Counts its drops in a shared counter
+#[derive(Debug)]
+struct Tracked {
+    value: u32,
+    drops: Rc<Cell<usize>>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+    }
+}
+
+/// Counts its drops and panics in `Drop` if armed
+struct Bomb {
+    value: u32,
+    armed: bool,
+    drops: Rc<Cell<usize>>,
+}
+
+impl Drop for Bomb {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+        if self.armed {
+            panic!("bomb {} exploded", self.value);
+        }
+    }
+}
+
+#[repr(align(128))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CacheAligned(u64);
+
+#[repr(align(4096))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PageAligned(u8);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Plain(u32);
+
+#[derive(Debug, PartialEq)]
+struct Marker;
+
+component!(Tracked, Bomb, Plain, CacheAligned, PageAligned, Marker);
+
+fn system() -> Arc<dyn ChunkAllocator> {
+    Arc::new(SystemChunkAllocator)
+}
+
+/// Small arena without scratch space, plus the same arena as a chunk allocator
+fn small_arena() -> (Arc<Arena>, Arc<dyn ChunkAllocator>) {
+    let arena = Arc::new(Arena::with_scratch(64 * 1024, 64 * 1024, 0));
+    (arena.clone(), arena)
+}
+
+fn tracked_chunk(capacity: usize, values: &[u32]) -> (Chunk<Tracked>, Rc<Cell<usize>>) {
+    let drops = Rc::new(Cell::new(0));
+    let mut chunk = Chunk::new(&system(), capacity);
+    for &value in values {
+        chunk.add(Tracked { value, drops: drops.clone() }).unwrap();
+    }
+    (chunk, drops)
+}
+
+fn values(chunk: &Chunk<Tracked>) -> Vec<u32> {
+    chunk.as_slice().iter().map(|tracked| tracked.value).collect()
+}
+
+fn bomb_chunk(values: &[u32], armed: u32) -> (Chunk<Bomb>, Rc<Cell<usize>>) {
+    let drops = Rc::new(Cell::new(0));
+    let mut chunk = Chunk::new(&system(), values.len());
+    for &value in values {
+        chunk.add(Bomb { value, armed: value == armed, drops: drops.clone() }).unwrap();
+    }
+    (chunk, drops)
+}
+
+#[test]
+fn add_fills_slots_in_order_until_full() {
+    let mut chunk = Chunk::<Plain>::new(&system(), 3);
+
+    assert_eq!(chunk.add(Plain(10)), Some(0));
+    assert_eq!(chunk.add(Plain(11)), Some(1));
+    assert_eq!(chunk.try_add(Plain(12)), Ok(2));
+    assert_eq!(chunk.try_add(Plain(13)), Err(EcsError::CapacityExceeded { capacity: 3 }));
+
+    assert_eq!(chunk.count(), 3);
+    assert_eq!(chunk.as_slice(), &[Plain(10), Plain(11), Plain(12)]);
+    assert_eq!(chunk.get(2), Some(&Plain(12)));
+    assert_eq!(chunk.get(3), None);
+}
+
+#[test]
+fn rejected_add_drops_the_component() {
+    let (mut chunk, drops) = tracked_chunk(1, &[1]);
+
+    assert!(chunk.add(Tracked { value: 2, drops: drops.clone() }).is_none());
+    assert_eq!(drops.get(), 1);
+    assert_eq!(values(&chunk), [1]);
+}
+
+#[test]
+fn set_replaces_and_drops_the_old_component_once() {
+    let (mut chunk, drops) = tracked_chunk(4, &[1, 2, 3]);
+
+    assert!(chunk.set(1, Tracked { value: 20, drops: drops.clone() }));
+
+    assert_eq!(drops.get(), 1);
+    assert_eq!(values(&chunk), [1, 20, 3]);
+
+    drop(chunk);
+    assert_eq!(drops.get(), 4);
+}
+
+#[test]
+fn set_at_count_appends() {
+    let (mut chunk, drops) = tracked_chunk(4, &[1]);
+
+    assert!(chunk.set(1, Tracked { value: 2, drops: drops.clone() }));
+
+    assert_eq!(drops.get(), 0);
+    assert_eq!(values(&chunk), [1, 2]);
+}
+
+#[test]
+fn set_past_count_never_leaves_a_gap() {
+    let (mut chunk, drops) = tracked_chunk(8, &[1, 2]);
+
+    let result = chunk.try_set(5, Tracked { value: 6, drops: drops.clone() });
+
+    assert!(matches!(result, Err(EcsError::MissingComponent(_))));
+    assert_eq!(drops.get(), 1, "the rejected component is dropped, nothing else");
+    assert_eq!(chunk.count(), 2);
+    assert_eq!(values(&chunk), [1, 2]);
+}
+
+#[test]
+fn set_past_capacity_is_rejected() {
+    let (mut chunk, drops) = tracked_chunk(2, &[1, 2]);
+
+    let result = chunk.try_set(2, Tracked { value: 3, drops: drops.clone() });
+
+    assert_eq!(result.err(), Some(EcsError::CapacityExceeded { capacity: 2 }));
+    assert_eq!(values(&chunk), [1, 2]);
+}
+
+#[test]
+fn swap_remove_moves_the_last_component_without_extra_drops() {
+    let (mut chunk, drops) = tracked_chunk(4, &[1, 2, 3, 4]);
+
+    assert!(chunk.swap_remove(1));
+    assert_eq!(drops.get(), 1);
+    assert_eq!(values(&chunk), [1, 4, 3]);
+
+    assert!(chunk.swap_remove(2));
+    assert_eq!(drops.get(), 2);
+    assert_eq!(values(&chunk), [1, 4]);
+
+    assert!(!chunk.swap_remove(2));
+    assert!(matches!(chunk.try_swap_remove(2), Err(EcsError::MissingComponent(_))));
+
+    drop(chunk);
+    assert_eq!(drops.get(), 4);
+}
+
+#[test]
+fn swap_take_hands_out_the_component_without_dropping_it() {
+    let (mut chunk, drops) = tracked_chunk(3, &[1, 2, 3]);
+
+    let taken = chunk.swap_take(0).unwrap();
+
+    assert_eq!(taken.value, 1);
+    assert_eq!(drops.get(), 0);
+    assert_eq!(values(&chunk), [3, 2]);
+    assert!(chunk.swap_take(2).is_none());
+
+    drop(taken);
+    drop(chunk);
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+fn remove_keeps_the_order() {
+    let (mut chunk, drops) = tracked_chunk(4, &[1, 2, 3, 4]);
+
+    assert!(chunk.remove(1));
+    assert_eq!(values(&chunk), [1, 3, 4]);
+
+    assert!(chunk.remove(2));
+    assert_eq!(values(&chunk), [1, 3]);
+
+    assert!(!chunk.remove(2));
+    assert_eq!(drops.get(), 2);
+}
+
+#[test]
+fn clear_and_drop_release_every_component_once() {
+    let (mut chunk, drops) = tracked_chunk(4, &[1, 2, 3]);
+
+    chunk.clear();
+    assert_eq!(drops.get(), 3);
+    assert_eq!(chunk.count(), 0);
+    assert!(chunk.as_slice().is_empty());
+
+    chunk.add(Tracked { value: 4, drops: drops.clone() }).unwrap();
+    drop(chunk);
+    assert_eq!(drops.get(), 4);
+}
+
+#[test]
+fn mutable_access_changes_the_stored_component() {
+    let (mut chunk, _drops) = tracked_chunk(3, &[1, 2, 3]);
+
+    chunk.get_mut(0).unwrap().value = 10;
+    chunk.as_mut_slice()[2].value = 30;
+    unsafe { chunk.get_ptr(1).unwrap().as_mut().value = 20 };
+
+    assert_eq!(values(&chunk), [10, 20, 30]);
+    assert!(chunk.get_mut(3).is_none());
+    assert!(chunk.get_ptr(3).is_none());
+}
+
+#[test]
+fn panicking_destructor_in_swap_remove_keeps_the_chunk_consistent() {
+    let (mut chunk, drops) = bomb_chunk(&[1, 2, 3], 1);
+
+    let result = catch_unwind(AssertUnwindSafe(|| chunk.swap_remove(0)));
+
+    assert!(result.is_err());
+    assert_eq!(drops.get(), 1);
+    assert_eq!(chunk.count(), 2);
+    assert_eq!(chunk.as_slice().iter().map(|bomb| bomb.value).collect::<Vec<_>>(), [3, 2]);
+
+    drop(chunk);
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+fn panicking_destructor_in_remove_keeps_the_chunk_consistent() {
+    let (mut chunk, drops) = bomb_chunk(&[1, 2, 3], 2);
+
+    let result = catch_unwind(AssertUnwindSafe(|| chunk.remove(1)));
+
+    assert!(result.is_err());
+    assert_eq!(chunk.as_slice().iter().map(|bomb| bomb.value).collect::<Vec<_>>(), [1, 3]);
+
+    drop(chunk);
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+fn panicking_destructor_in_set_keeps_the_new_component() {
+    let (mut chunk, drops) = bomb_chunk(&[1, 2], 2);
+
+    let replacement = Bomb { value: 20, armed: false, drops: drops.clone() };
+    let result = catch_unwind(AssertUnwindSafe(|| chunk.set(1, replacement)));
+
+    assert!(result.is_err());
+    assert_eq!(chunk.get(1).map(|bomb| bomb.value), Some(20));
+
+    drop(chunk);
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+fn panicking_destructor_in_clear_still_drops_the_others() {
+    let (mut chunk, drops) = bomb_chunk(&[1, 2, 3, 4], 2);
+
+    let result = catch_unwind(AssertUnwindSafe(|| chunk.clear()));
+
+    assert!(result.is_err());
+    assert_eq!(drops.get(), 4);
+    assert_eq!(chunk.count(), 0);
+
+    drop(chunk);
+    assert_eq!(drops.get(), 4);
+}
+
+#[test]
+fn panicking_destructor_while_dropping_still_frees_the_memory() {
+    let (arena, allocator) = small_arena();
+    let drops = Rc::new(Cell::new(0));
+
+    let mut chunk = Chunk::<Bomb>::new(&allocator, 4);
+    for value in 0..3 {
+        chunk.add(Bomb { value, armed: value == 0, drops: drops.clone() }).unwrap();
+    }
+    assert_eq!(arena.stats().allocation_count, 1);
+
+    let result = catch_unwind(AssertUnwindSafe(move || drop(chunk)));
+
+    assert!(result.is_err());
+    assert_eq!(drops.get(), 3);
+    assert_eq!(arena.stats().allocation_count, 0);
+    assert!(arena.leak_report().is_empty());
+}
+
+#[test]
+fn over_aligned_components_are_aligned_in_every_slot() {
+    let mut chunk = Chunk::<CacheAligned>::new(&system(), 5);
+    for value in 0..5 {
+        chunk.add(CacheAligned(value)).unwrap();
+    }
+
+    for index in 0..5 {
+        let ptr = chunk.get_ptr(index).unwrap();
+        assert!((ptr.as_ptr() as usize).is_multiple_of(128));
+    }
+
+    chunk.swap_remove(1);
+    assert_eq!(chunk.as_slice(), &[CacheAligned(0), CacheAligned(4), CacheAligned(2), CacheAligned(3)]);
+}
+
+#[test]
+fn over_aligned_components_from_an_arena_are_aligned() {
+    let (_, allocator) = small_arena();
+
+    // Shift the free space off any large alignment first
+    let _padding = Chunk::<Plain>::new(&allocator, 3);
+
+    let mut cache_aligned = Chunk::<CacheAligned>::new(&allocator, 3);
+    let mut page_aligned = Chunk::<PageAligned>::new(&allocator, 2);
+    cache_aligned.add(CacheAligned(7)).unwrap();
+    page_aligned.add(PageAligned(1)).unwrap();
+    page_aligned.add(PageAligned(2)).unwrap();
+
+    assert!((cache_aligned.as_ptr() as usize).is_multiple_of(128));
+    assert!((page_aligned.as_ptr() as usize).is_multiple_of(4096));
+    assert_eq!(cache_aligned.as_slice(), &[CacheAligned(7)]);
+    assert_eq!(page_aligned.as_slice(), &[PageAligned(1), PageAligned(2)]);
+}
+
+#[test]
+fn chunk_keeps_its_allocator_alive() {
+    let (arena, allocator) = small_arena();
+    let mut chunk = Chunk::<Plain>::new(&allocator, 4);
+    drop(allocator);
+
+    // The chunk holds the last reference besides this one
+    assert_eq!(Arc::strong_count(&arena), 2);
+    drop(arena);
+
+    chunk.add(Plain(1)).unwrap();
+    assert_eq!(chunk.as_slice(), &[Plain(1)]);
+}
+
+#[test]
+fn zero_sized_components_are_counted() {
+    let mut chunk = Chunk::<Marker>::new(&system(), 3);
+
+    assert_eq!(chunk.add(Marker), Some(0));
+    assert_eq!(chunk.add(Marker), Some(1));
+    assert!(chunk.swap_remove(0));
+
+    assert_eq!(chunk.count(), 1);
+    assert_eq!(chunk.as_slice(), &[Marker]);
+}
+
+#[test]
+fn zero_capacity_chunk_holds_nothing() {
+    let mut chunk = Chunk::<Plain>::new(&system(), 0);
+
+    assert_eq!(chunk.try_add(Plain(1)), Err(EcsError::CapacityExceeded { capacity: 0 }));
+    assert!(chunk.as_slice().is_empty());
+}
@@ -0,0 +1,159 @@
+//! Arena compaction: every relocatable block that moves is reported, its contents
+//! move with it, pinned blocks stay put, and the free space ends up in one tail.
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::memory::arena::Arena;
+use boyko_ecs::ecs::memory::chunk::Chunk;
+use boyko_ecs::ecs::memory::chunk_allocator::ChunkAllocator;
+
+/// Blocks above the largest thread cache class, so they go straight to the free blocks
+const BLOCK: usize = 8 * 1024;
+
+const OWNER: &str = "compaction";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Position([f32; 3]);
+
+impl Component for Position {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+fn block(size: usize) -> Layout {
+    Layout::from_size_align(size, 8).unwrap()
+}
+
+/// Arena that never grows past its first region
+fn fixed_arena() -> Arena {
+    Arena::with_limit(32 * BLOCK, 32 * BLOCK)
+}
+
+fn filled_block(arena: &Arena, fill: u8) -> NonNull<u8> {
+    let ptr = arena.try_allocate_relocatable(block(BLOCK), OWNER).unwrap();
+    unsafe { ptr.as_ptr().write_bytes(fill, BLOCK) };
+    ptr
+}
+
+fn has_fill(ptr: NonNull<u8>, fill: u8) -> bool {
+    let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), BLOCK) };
+    bytes.iter().all(|&byte| byte == fill)
+}
+
+#[test]
+fn every_moved_block_is_reported_with_its_data() {
+    let arena = fixed_arena();
+    let blocks: Vec<_> = (0..8).map(|index| filled_block(&arena, index)).collect();
+
+    // Every other block leaves a hole behind
+    for &ptr in blocks.iter().step_by(2) {
+        arena.deallocate_relocatable(ptr, block(BLOCK), OWNER);
+    }
+
+    let relocations = unsafe { arena.compact() };
+    assert_eq!(relocations.len(), 4);
+
+    let mut moved = Vec::new();
+    for (index, &old) in blocks.iter().enumerate().skip(1).step_by(2) {
+        let new = relocations.new_address(old).expect("block behind a hole has to move");
+        assert!(new < old);
+        assert!(has_fill(new, index as u8), "block {index} lost its data");
+        moved.push(new);
+    }
+
+    // The blocks are packed in their old order, one free block is left behind them
+    assert!(moved.windows(2).all(|pair| pair[0] < pair[1]));
+    let stats = arena.stats();
+    assert_eq!(stats.free_block_count, 1);
+    assert_eq!(stats.largest_free_block, stats.free_bytes);
+    assert_eq!(stats.fragmentation, 0.0);
+
+    for ptr in moved {
+        arena.deallocate_relocatable(ptr, block(BLOCK), OWNER);
+    }
+    assert_eq!(arena.stats().used_bytes, 0);
+}
+
+#[test]
+fn packed_arena_moves_nothing() {
+    let arena = fixed_arena();
+    let blocks: Vec<_> = (0..4).map(|index| filled_block(&arena, index)).collect();
+
+    let relocations = unsafe { arena.compact() };
+    assert!(relocations.is_empty());
+    assert!(blocks.iter().all(|&ptr| relocations.new_address(ptr).is_none()));
+    assert_eq!(arena.stats().free_block_count, 1);
+
+    for ptr in blocks {
+        arena.deallocate_relocatable(ptr, block(BLOCK), OWNER);
+    }
+}
+
+#[test]
+fn pinned_blocks_stay_in_place() {
+    let arena = fixed_arena();
+    let first = filled_block(&arena, 1);
+    let hole = filled_block(&arena, 2);
+    let pinned = arena.allocate_layout(block(BLOCK));
+    unsafe { pinned.as_ptr().write_bytes(3, BLOCK) };
+    let last = filled_block(&arena, 4);
+    arena.deallocate_relocatable(first, block(BLOCK), OWNER);
+    arena.deallocate_relocatable(hole, block(BLOCK), OWNER);
+
+    // The last block cannot pass the pinned one, so it fills the hole in front of it
+    let relocations = unsafe { arena.compact() };
+    assert_eq!(relocations.len(), 1);
+    assert_eq!(relocations.new_address(pinned), None);
+    assert!(has_fill(pinned, 3));
+
+    let last = relocations.new_address(last).expect("last block moves into the hole");
+    assert!(last < pinned);
+    assert!(has_fill(last, 4));
+
+    // The rest of the hole stays free, the space behind the pinned block is one tail
+    assert_eq!(arena.stats().free_block_count, 2);
+
+    arena.deallocate(pinned, block(BLOCK));
+    arena.deallocate_relocatable(last, block(BLOCK), OWNER);
+    assert_eq!(arena.stats().free_block_count, 1);
+}
+
+#[test]
+fn relocated_chunks_keep_their_components() {
+    let arena = Arc::new(fixed_arena());
+    let allocator: Arc<dyn ChunkAllocator> = arena.clone();
+
+    let mut chunks: Vec<_> = (0..4)
+        .map(|index| {
+            let mut chunk = Chunk::<Position>::new(&allocator, 512);
+            for slot in 0..512 {
+                chunk.add(Position([index as f32, slot as f32, 0.0]));
+            }
+            chunk
+        })
+        .collect();
+    chunks.remove(0);
+    chunks.remove(1);
+
+    let old: Vec<_> = chunks.iter().map(Chunk::as_ptr).collect();
+    let relocations = unsafe { arena.compact() };
+    assert_eq!(relocations.len(), 2);
+
+    for (chunk, index) in chunks.iter_mut().zip([1.0, 3.0]) {
+        chunk.relocate(&relocations);
+        assert_eq!(chunk.count(), 512);
+        for (slot, position) in chunk.as_slice().iter().enumerate() {
+            assert_eq!(*position, Position([index, slot as f32, 0.0]));
+        }
+    }
+    assert!(chunks.iter().zip(old).all(|(chunk, old)| chunk.as_ptr() < old));
+
+    // Relocated chunks still free their memory where it lives now
+    drop(chunks);
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.free_block_count, 1);
+}
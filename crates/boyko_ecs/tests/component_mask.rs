@@ -0,0 +1,87 @@
+//! `ComponentMask` across its inline limit: IDs on both sides of
+//! `COMPONENT_MASK_INLINE_BITS` behave the same, and a spilled mask equals and
+//! hashes like an inline one with the same components.
+
+use std::collections::HashSet;
+use std::hash::{BuildHasher, RandomState};
+use boyko_ecs::ecs::constants::COMPONENT_MASK_INLINE_BITS;
+use boyko_ecs::ecs::core::component_mask::ComponentMask;
+
+const LIMIT: usize = COMPONENT_MASK_INLINE_BITS;
+
+/// IDs right around the word and inline boundaries
+const EDGES: [usize; 8] = [0, 63, 64, LIMIT - 64, LIMIT - 1, LIMIT, LIMIT + 63, LIMIT + 64];
+
+fn mask(ids: &[usize]) -> ComponentMask {
+    ids.iter().copied().collect()
+}
+
+#[test]
+fn ids_on_both_sides_of_the_limit_are_kept() {
+    let mut mask = ComponentMask::new();
+    for id in EDGES {
+        assert!(!mask.contains(id));
+        mask.insert(id);
+        assert!(mask.contains(id));
+    }
+
+    assert_eq!(mask.len(), EDGES.len());
+    assert!(mask.iter().eq(EDGES));
+    assert!(!mask.contains(LIMIT + 1) && !mask.contains(LIMIT - 2) && !mask.contains(10 * LIMIT));
+
+    for id in EDGES {
+        mask.remove(id);
+        assert!(!mask.contains(id));
+    }
+    assert!(mask.is_empty());
+}
+
+#[test]
+fn subsets_span_the_limit() {
+    let inline = mask(&[1, LIMIT - 1]);
+    let spilled = mask(&[1, LIMIT - 1, LIMIT]);
+    let far = mask(&[1, 4 * LIMIT]);
+
+    assert!(spilled.contains_all(&inline));
+    assert!(!inline.contains_all(&spilled));
+    assert!(!spilled.contains_all(&far));
+    assert!(!far.contains_all(&inline));
+    assert!(far.contains_all(&mask(&[4 * LIMIT])));
+
+    // Every mask holds the empty one and itself
+    for mask in [&inline, &spilled, &far] {
+        assert!(mask.contains_all(&ComponentMask::new()));
+        assert!(mask.contains_all(mask));
+    }
+
+    assert!(inline.intersects(&far));
+    assert!(!mask(&[LIMIT - 1]).intersects(&mask(&[LIMIT])));
+    assert!(mask(&[LIMIT, 2]).intersects(&mask(&[LIMIT])));
+}
+
+#[test]
+fn spilled_and_inline_masks_with_equal_bits_are_equal() {
+    let inline = mask(&[3, 64, LIMIT - 1]);
+
+    // Inserting past the limit spills to the heap, removing it again keeps it there
+    let mut spilled = inline.clone();
+    spilled.insert(LIMIT + 100);
+    assert_ne!(spilled, inline);
+    spilled.remove(LIMIT + 100);
+
+    assert_eq!(spilled, inline);
+    assert_eq!(inline, spilled);
+    assert!(spilled.iter().eq(inline.iter()));
+
+    let state = RandomState::new();
+    assert_eq!(state.hash_one(&spilled), state.hash_one(&inline));
+
+    let set: HashSet<_> = [inline, spilled].into_iter().collect();
+    assert_eq!(set.len(), 1);
+
+    // The same holds for the empty masks
+    let mut empty = mask(&[2 * LIMIT]);
+    empty.remove(2 * LIMIT);
+    assert_eq!(empty, ComponentMask::new());
+    assert_eq!(state.hash_one(&empty), state.hash_one(ComponentMask::new()));
+}
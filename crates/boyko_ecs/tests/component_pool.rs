@@ -0,0 +1,245 @@
+//! `ComponentPool<T>` bookkeeping across growth, slot reuse, chunk release and compaction:
+//! indices handed out before the pool grows keep pointing at the same components,
+//! freed slots are refilled lowest chunk first, empty chunks go back to the allocator,
+//! and compaction reports every component it moves.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use boyko_ecs::ecs::constants::{
+    COMPACTION_THRESHOLD, DEFAULT_CHUNKS_PER_POOL, MAX_EMPTY_CHUNKS_RATIO, MIN_COMPONENTS_FOR_COMPACTION,
+};
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::memory::chunk_allocator::{ChunkAllocator, SystemChunkAllocator};
+use boyko_ecs::ecs::memory::component_index::UnitId;
+use boyko_ecs::ecs::memory::component_pool::{ComponentPool, ComponentStorage};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Value(u64);
+
+impl Component for Value {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+fn system() -> Arc<dyn ChunkAllocator> {
+    Arc::new(SystemChunkAllocator)
+}
+
+/// Adds `count` components numbered from `first`, returning their indices
+fn fill(pool: &mut ComponentPool<Value>, first: u64, count: u64) -> Vec<UnitId> {
+    (first..first + count).map(|value| pool.add(Value(value)).unwrap()).collect()
+}
+
+#[test]
+fn pool_grows_past_the_default_chunk_count() {
+    let mut pool = ComponentPool::<Value>::new(&system(), DEFAULT_CHUNKS_PER_POOL, 2);
+    let capacity = DEFAULT_CHUNKS_PER_POOL * 2;
+
+    let before = fill(&mut pool, 0, capacity as u64);
+    assert!(pool.is_full());
+    assert_eq!(pool.remaining_capacity(), 0);
+    assert_eq!(pool.chunks_count(), DEFAULT_CHUNKS_PER_POOL);
+
+    let grown = pool.add(Value(capacity as u64)).unwrap();
+    assert_eq!(grown.chunk_index(), DEFAULT_CHUNKS_PER_POOL);
+    assert!(pool.chunks_count() > DEFAULT_CHUNKS_PER_POOL);
+    assert!(!pool.is_full());
+    assert_eq!(pool.remaining_capacity(), pool.chunks_count() * 2 - capacity - 1);
+
+    // Growing appends chunks, nothing that was already stored moves
+    for (value, index) in before.iter().enumerate() {
+        assert_eq!(pool.get(*index), Some(&Value(value as u64)), "{index:?}");
+    }
+    assert_eq!(pool.get(grown), Some(&Value(capacity as u64)));
+}
+
+#[test]
+fn capacity_stays_exact_while_filling_grown_chunks() {
+    let mut pool = ComponentPool::<Value>::new(&system(), 1, 4);
+    let mut indices = Vec::new();
+
+    for value in 0..100 {
+        let remaining = pool.remaining_capacity();
+        assert_eq!(pool.is_full(), remaining == 0, "before value {value}");
+
+        indices.push(pool.add(Value(value)).unwrap());
+        assert_eq!(pool.count(), value as usize + 1);
+        assert_eq!(pool.remaining_capacity(), pool.allocated_chunks_count() * 4 - pool.count());
+    }
+
+    for (value, index) in indices.iter().enumerate() {
+        assert_eq!(pool.get(*index), Some(&Value(value as u64)));
+    }
+}
+
+#[test]
+fn reserve_grows_once_for_a_batch() {
+    let mut pool = ComponentPool::<Value>::new(&system(), 2, 8);
+    let before = fill(&mut pool, 0, 10);
+
+    pool.try_reserve(100).unwrap();
+    let chunks = pool.chunks_count();
+    assert!(pool.remaining_capacity() >= 100);
+
+    fill(&mut pool, 10, 100);
+    assert_eq!(pool.chunks_count(), chunks);
+    for (value, index) in before.iter().enumerate() {
+        assert_eq!(pool.get(*index), Some(&Value(value as u64)));
+    }
+}
+
+#[test]
+fn freed_slots_are_refilled_lowest_chunk_first() {
+    let mut pool = ComponentPool::<Value>::new(&system(), 4, 4);
+    let indices = fill(&mut pool, 0, 16);
+    assert!(pool.is_full());
+
+    // Free one slot in chunk 2 and one in chunk 0
+    assert!(pool.swap_remove(indices[9]));
+    assert!(pool.swap_remove(indices[1]));
+    assert_eq!(pool.current_chunk_index(), 0);
+
+    let refilled = pool.add(Value(100)).unwrap();
+    assert_eq!(refilled.chunk_index(), 0);
+    assert_eq!(pool.get(refilled), Some(&Value(100)));
+
+    let refilled = pool.add(Value(101)).unwrap();
+    assert_eq!(refilled.chunk_index(), 2);
+    assert!(pool.is_full());
+    assert_eq!(pool.chunks_count(), 4);
+}
+
+#[test]
+fn churn_keeps_reusing_the_same_chunks() {
+    let mut pool = ComponentPool::<Value>::new(&system(), 4, 8);
+    fill(&mut pool, 0, 32);
+
+    for round in 0..1000 {
+        let removed = UnitId::new(round % 4, round * 7 % 8);
+        let value = pool.swap_take(removed).unwrap();
+
+        // Every other chunk is full, so the freed slot is the only place to go
+        let added = pool.add(value).unwrap();
+        assert_eq!(added.chunk_index(), removed.chunk_index(), "round {round}");
+        assert!(pool.is_full());
+    }
+
+    assert_eq!(pool.chunks_count(), 4);
+    assert_eq!(pool.count(), 32);
+    let mut values: Vec<_> = (0..4).flat_map(|chunk| pool.chunk_components(chunk).unwrap().to_vec()).collect();
+    values.sort_by_key(|value| value.0);
+    assert_eq!(values, (0..32).map(Value).collect::<Vec<_>>());
+}
+
+#[test]
+fn empty_chunks_are_released_past_the_ratio() {
+    assert_eq!(MAX_EMPTY_CHUNKS_RATIO, 0.2);
+    let mut pool = ComponentPool::<Value>::new(&system(), 10, 2);
+    fill(&mut pool, 0, 20);
+
+    // Empty the chunks from the last one down. Two empty chunks out of ten are still
+    // within the ratio, from then on everything but the last emptied chunk is released.
+    let mut allocated = Vec::new();
+    for chunk in (0..10).rev() {
+        pool.swap_remove(UnitId::new(chunk, 1));
+        pool.swap_remove(UnitId::new(chunk, 0));
+        allocated.push(pool.allocated_chunks_count());
+    }
+    assert_eq!(allocated, [10, 10, 8, 7, 6, 5, 4, 3, 2, 1]);
+
+    assert_eq!(pool.count(), 0);
+    assert_eq!(pool.chunks_count(), 10);
+    assert_eq!(pool.chunk_components(9), Some(&[][..]));
+
+    // Released chunks are allocated again when the pool refills
+    let refilled = fill(&mut pool, 0, 20);
+    assert_eq!(pool.chunks_count(), 10);
+    assert_eq!(pool.allocated_chunks_count(), 10);
+    for (value, index) in refilled.iter().enumerate() {
+        assert_eq!(pool.get(*index), Some(&Value(value as u64)));
+    }
+}
+
+/// Every stored component by its chunk and inland index
+fn snapshot(pool: &ComponentPool<Value>) -> HashMap<(usize, usize), Value> {
+    (0..pool.chunks_count())
+        .flat_map(|chunk| {
+            let components = pool.chunk_components(chunk).unwrap();
+            components.iter().enumerate().map(move |(inland, value)| ((chunk, inland), *value))
+        })
+        .collect()
+}
+
+fn key(index: UnitId) -> (usize, usize) {
+    (index.chunk_index(), index.inland_index())
+}
+
+#[test]
+fn compaction_remaps_every_moved_component() {
+    let mut pool = ComponentPool::<Value>::new(&system(), 8, 4);
+    fill(&mut pool, 0, 32);
+
+    // Leave chunk `i` with `i % 4 + 1` components
+    for chunk in 0..8 {
+        for inland in (chunk % 4 + 1..4).rev() {
+            pool.swap_remove(UnitId::new(chunk, inland));
+        }
+    }
+    assert!(pool.needs_compaction());
+
+    let before = snapshot(&pool);
+    let remap = pool.compact();
+    assert!(!remap.is_empty());
+    let after = snapshot(&pool);
+
+    // Components missing from the remap kept their index
+    let moved: HashMap<_, _> = remap.iter().map(|&(old, new)| (key(old), new)).collect();
+    assert_eq!(moved.len(), remap.len(), "a component moved twice");
+    for (&(chunk, inland), value) in &before {
+        let new = moved.get(&(chunk, inland)).copied().unwrap_or(UnitId::new(chunk, inland));
+        assert_eq!(after.get(&key(new)), Some(value), "({chunk}, {inland}) -> {new:?}");
+        assert_eq!(pool.get(new), Some(value));
+    }
+    assert_eq!(after.len(), before.len());
+
+    let partial = (0..pool.chunks_count())
+        .filter_map(|chunk| pool.chunk_component_count(chunk))
+        .filter(|&count| count > 0 && count < 4)
+        .count();
+    assert!(partial <= 1, "{partial} partial chunks left");
+    assert!(!pool.needs_compaction());
+}
+
+#[test]
+fn compaction_triggers_past_the_threshold() {
+    assert_eq!((COMPACTION_THRESHOLD, MIN_COMPONENTS_FOR_COMPACTION), (0.25, 16));
+    let mut pool = ComponentPool::<Value>::new(&system(), 8, 4);
+    fill(&mut pool, 0, 32);
+
+    // 24 components over 8 chunks fit into 6, exactly the threshold
+    for chunk in 0..8 {
+        pool.swap_remove(UnitId::new(chunk, 3));
+    }
+    assert!(!pool.needs_compaction());
+
+    // 20 components fit into 5 chunks, 3 out of 8 could be freed
+    for chunk in 0..4 {
+        pool.swap_remove(UnitId::new(chunk, 2));
+    }
+    assert!(pool.needs_compaction());
+
+    // However fragmented, a pool below the minimum size is left alone
+    let mut small = ComponentPool::<Value>::new(&system(), 8, 4);
+    fill(&mut small, 0, 32);
+    for chunk in 0..8 {
+        for inland in (1..4).rev() {
+            small.swap_remove(UnitId::new(chunk, inland));
+        }
+    }
+    for chunk in 0..8 {
+        assert_eq!(small.chunk_component_count(chunk), Some(1));
+    }
+    assert_eq!(small.count(), 8);
+    assert!(!small.needs_compaction());
+}
@@ -0,0 +1,69 @@
+//! `debug-arena` checks: writes past either end of an allocation are reported as
+//! corruption, and new and freed memory carry their fill patterns.
+#![cfg(feature = "debug-arena")]
+
+use std::alloc::Layout;
+use boyko_ecs::ecs::constants::{DEBUG_ALLOC_BYTE, DEBUG_CANARY_BYTE, DEBUG_POISON_BYTE};
+use boyko_ecs::ecs::error::EcsError;
+use boyko_ecs::ecs::memory::arena::Arena;
+
+/// Blocks above the largest thread cache class, so freed memory is not reused right away
+const BLOCK: usize = 8 * 1024;
+
+fn block(size: usize) -> Layout {
+    Layout::from_size_align(size, 8).unwrap()
+}
+
+#[test]
+fn one_byte_overrun_is_corruption() {
+    let arena = Arena::new();
+    let ptr = arena.allocate_layout(block(100));
+    unsafe { ptr.as_ptr().write_bytes(1, 100) };
+    assert_eq!(arena.validate(), Ok(()));
+
+    unsafe { ptr.as_ptr().add(100).write(0) };
+    assert_eq!(arena.validate(), Err(EcsError::MemoryCorruption { address: ptr.as_ptr() as usize }));
+
+    // With the canary restored the block is whole again and can be freed
+    unsafe { ptr.as_ptr().add(100).write(DEBUG_CANARY_BYTE) };
+    assert_eq!(arena.validate(), Ok(()));
+    arena.deallocate(ptr, block(100));
+}
+
+#[test]
+fn one_byte_underrun_is_corruption() {
+    let arena = Arena::new();
+    let ptr = arena.allocate_layout(block(BLOCK));
+
+    unsafe { ptr.as_ptr().sub(1).write(0) };
+    assert_eq!(arena.validate(), Err(EcsError::MemoryCorruption { address: ptr.as_ptr() as usize }));
+
+    unsafe { ptr.as_ptr().sub(1).write(DEBUG_CANARY_BYTE) };
+    arena.deallocate(ptr, block(BLOCK));
+}
+
+#[test]
+#[should_panic(expected = "written out of bounds")]
+fn freeing_a_damaged_block_panics() {
+    let arena = Arena::new();
+    let ptr = arena.allocate_layout(block(64));
+
+    unsafe { ptr.as_ptr().add(64).write(0) };
+    arena.deallocate(ptr, block(64));
+}
+
+#[test]
+fn new_memory_is_filled_and_freed_memory_poisoned() {
+    let arena = Arena::new();
+    let ptr = arena.allocate_layout(block(BLOCK));
+    let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), BLOCK) };
+    assert!(bytes.iter().all(|&byte| byte == DEBUG_ALLOC_BYTE));
+
+    unsafe { ptr.as_ptr().write_bytes(0x42, BLOCK) };
+    arena.deallocate(ptr, block(BLOCK));
+
+    // The arena still owns the memory, a dangling read sees the poison instead of stale data
+    let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), BLOCK) };
+    assert!(bytes.iter().all(|&byte| byte == DEBUG_POISON_BYTE));
+    assert_eq!(arena.validate(), Ok(()));
+}
@@ -0,0 +1,146 @@
+//! `EcsMaster` keeps the entity index pointing at the right rows while archetypes
+//! move their rows around, and rejects handles whose ID was reused.
+
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::core::ecs_master::EcsMaster;
+use boyko_ecs::ecs::core::entity::{Entities, Entity};
+
+/// Four rows per chunk, so a few dozen entities are enough to fragment an archetype
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Small(u32);
+
+impl Component for Small {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+
+    fn chunk_capacity() -> Option<usize> {
+        Some(4)
+    }
+}
+
+/// Moves an entity into a second archetype
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Tag(u32);
+
+impl Component for Tag {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+/// Every entity resolves to its own row and component
+fn assert_index_consistent(world: &EcsMaster, alive: &[(Entity, u32)]) {
+    for &(entity, value) in alive {
+        let location = world.location(entity).unwrap();
+        let archetype = world.archetype(location.archetype_id).unwrap();
+        assert_eq!(archetype.entities().get(location.row), Some(&entity), "{location:?}");
+        assert_eq!(world.get::<Small>(entity), Some(&Small(value)), "{entity:?}");
+    }
+    assert_eq!(world.entity_count(), alive.len());
+}
+
+#[test]
+fn compaction_keeps_the_entity_index_consistent() {
+    let mut world = EcsMaster::new();
+    let mut alive: Vec<_> = (0..64)
+        .map(|value| {
+            let entity = world.spawn();
+            world.insert(entity, Small(value));
+            (entity, value)
+        })
+        .collect();
+    let archetype_id = world.location(alive[0].0).unwrap().archetype_id;
+    let chunks = world.archetype(archetype_id).unwrap().entities().chunks_count();
+
+    // Keeping every fourth entity would leave one row per chunk without compaction
+    for &(entity, value) in &alive {
+        if value % 4 != 0 {
+            assert!(world.despawn(entity));
+        }
+    }
+    alive.retain(|&(_, value)| value % 4 == 0);
+    assert_index_consistent(&world, &alive);
+
+    let archetype = world.archetype(archetype_id).unwrap();
+    assert!(!archetype.needs_compaction());
+    let used_chunks = (0..chunks)
+        .filter(|&chunk| archetype.entities().chunk_component_count(chunk).unwrap_or(0) > 0)
+        .count();
+    assert!(used_chunks < alive.len(), "rows were never compacted: {used_chunks} chunks in use");
+
+    // The moved rows keep working for later removals and inserts
+    for &(entity, value) in &alive {
+        if value % 3 == 0 {
+            assert!(world.despawn(entity));
+        }
+    }
+    alive.retain(|&(_, value)| value % 3 != 0);
+    for value in 100..110 {
+        let entity = world.spawn();
+        world.insert(entity, Small(value));
+        alive.push((entity, value));
+    }
+    assert_index_consistent(&world, &alive);
+}
+
+#[test]
+fn swap_remove_moves_the_last_entity_into_the_hole() {
+    let mut world = EcsMaster::new();
+    let alive: Vec<_> = (0..3)
+        .map(|value| {
+            let entity = world.spawn();
+            world.insert(entity, Small(value));
+            (entity, value)
+        })
+        .collect();
+    let (first, last) = (alive[0].0, alive[2].0);
+    let hole = world.location(first).unwrap();
+
+    // Despawning the first row pulls the last entity into it
+    assert!(world.despawn(first));
+    assert_eq!(world.location(last), Some(hole));
+    assert_index_consistent(&world, &alive[1..]);
+
+    // Moving to another archetype leaves a hole that is filled the same way
+    let (second, third) = (alive[1].0, alive[2].0);
+    let hole = world.location(third).unwrap();
+    world.insert(third, Tag(7));
+    assert_eq!(world.location(second), Some(hole));
+    assert_ne!(world.location(third).unwrap().archetype_id, hole.archetype_id);
+    assert_eq!(world.get::<Tag>(third), Some(&Tag(7)));
+    assert_index_consistent(&world, &alive[1..]);
+}
+
+#[test]
+fn stale_generations_are_rejected_after_reuse() {
+    let mut entities = Entities::new();
+    let first = entities.alloc();
+    assert!(entities.free(first).is_some());
+    assert_eq!(entities.free(first), None);
+
+    let reused = entities.alloc();
+    assert_eq!(reused.id, first.id);
+    assert_ne!(reused.generation, first.generation);
+    assert!(!entities.is_alive(first));
+    assert!(entities.is_alive(reused));
+    assert_eq!(entities.location(first), None);
+    assert_eq!(entities.len(), 1);
+
+    // The world refuses the old handle while the new one owns the ID
+    let mut world = EcsMaster::new();
+    let stale = world.spawn();
+    world.insert(stale, Small(1));
+    assert!(world.despawn(stale));
+    assert!(!world.despawn(stale));
+
+    let entity = world.spawn();
+    world.insert(entity, Small(2));
+    assert_eq!(entity.id, stale.id);
+    assert!(!world.is_alive(stale));
+    assert_eq!(world.location(stale), None);
+    assert_eq!(world.get::<Small>(stale), None);
+    assert!(!world.insert(stale, Small(3)));
+    assert_eq!(world.remove::<Small>(stale), None);
+    assert_index_consistent(&world, &[(entity, 2)]);
+}
@@ -0,0 +1,108 @@
+//! Every `EcsError` variant comes back from the `try_` API that reports it,
+//! and a failed call leaves the world as it was.
+
+use std::sync::Arc;
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::core::ecs_master::EcsMaster;
+use boyko_ecs::ecs::error::EcsError;
+use boyko_ecs::ecs::memory::arena::Arena;
+use boyko_ecs::ecs::memory::chunk::Chunk;
+use boyko_ecs::ecs::memory::chunk_allocator::{ChunkAllocator, SystemChunkAllocator};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Health(u32);
+
+impl Component for Health {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+/// Large enough that a single chunk of it does not fit into a small arena
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Blob([u8; 4096]);
+
+impl Component for Blob {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+#[test]
+fn dead_entities_are_stale() {
+    let mut world = EcsMaster::new();
+    let entity = world.spawn();
+    world.insert(entity, Health(10));
+    world.despawn(entity);
+
+    assert_eq!(world.try_insert(entity, Health(20)), Err(EcsError::StaleEntity(entity)));
+    assert_eq!(world.try_get::<Health>(entity).err(), Some(EcsError::StaleEntity(entity)));
+    assert_eq!(world.try_get_mut::<Health>(entity).err(), Some(EcsError::StaleEntity(entity)));
+    assert_eq!(world.try_remove::<Health>(entity).err(), Some(EcsError::StaleEntity(entity)));
+
+    // The ID is reused with a new generation, the old handle stays stale
+    let reused = world.spawn();
+    assert_eq!(reused.id, entity.id);
+    assert_eq!(world.try_get::<Health>(entity).err(), Some(EcsError::StaleEntity(entity)));
+}
+
+#[test]
+fn absent_components_are_missing() {
+    let mut world = EcsMaster::new();
+    let entity = world.spawn();
+    let missing = Some(EcsError::MissingComponent(Health::name()));
+
+    assert_eq!(world.try_get::<Health>(entity).err(), missing);
+    assert_eq!(world.try_get_mut::<Health>(entity).err(), missing);
+    assert_eq!(world.try_remove::<Health>(entity).err(), missing);
+    assert!(world.is_alive(entity));
+}
+
+#[test]
+fn full_chunks_exceed_their_capacity() {
+    let allocator: Arc<dyn ChunkAllocator> = Arc::new(SystemChunkAllocator);
+    let mut chunk = Chunk::<Health>::new(&allocator, 2);
+    chunk.try_add(Health(1)).unwrap();
+    chunk.try_add(Health(2)).unwrap();
+
+    assert_eq!(chunk.try_add(Health(3)), Err(EcsError::CapacityExceeded { capacity: 2 }));
+    assert_eq!(chunk.count(), 2);
+}
+
+#[test]
+fn capped_arena_runs_out_of_memory() {
+    let mut world = EcsMaster::with_arena(Arena::with_limit(64 * 1024, 64 * 1024));
+    let entity = world.spawn();
+
+    let error = world.try_insert(entity, Blob([7; 4096])).unwrap_err();
+    assert!(matches!(error, EcsError::OutOfMemory { .. }), "{error}");
+
+    // The entity keeps its place and can still take components that fit
+    assert!(!world.has::<Blob>(entity));
+    world.try_insert(entity, Health(5)).unwrap();
+    assert_eq!(world.get::<Health>(entity), Some(&Health(5)));
+}
+
+#[test]
+fn spawn_fails_once_the_empty_archetype_cannot_grow() {
+    let mut world = EcsMaster::with_arena(Arena::with_limit(24 * 1024, 24 * 1024));
+
+    let mut spawned = Vec::new();
+    let error = loop {
+        match world.try_spawn() {
+            Ok(entity) => spawned.push(entity),
+            Err(error) => break error,
+        }
+        assert!(spawned.len() < 1_000_000, "the arena never ran out");
+    };
+
+    assert!(matches!(error, EcsError::OutOfMemory { .. }), "{error}");
+    assert_eq!(world.entity_count(), spawned.len());
+
+    // The failed spawn did not take an entity ID, a freed row is reused right away
+    let despawned = spawned.pop().unwrap();
+    world.despawn(despawned);
+    let respawned = world.try_spawn().unwrap();
+    assert_eq!(respawned.id, despawned.id);
+    assert_eq!(world.entity_count(), spawned.len() + 1);
+}
@@ -0,0 +1,147 @@
+//! Zero-sized table components are archetype markers: they move the entity between
+//! archetypes like any other component, but never get a column.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId};
+use boyko_ecs::ecs::core::ecs_master::EcsMaster;
+use boyko_ecs::ecs::core::entity::Entity;
+use boyko_ecs::ecs::core::query::{With, Without};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Position([f32; 2]);
+
+impl Component for Position {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Health(u32);
+
+impl Component for Health {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Enemy;
+
+impl Component for Enemy {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+static BURNING_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Marker with drop glue, counted in `BURNING_DROPS`
+struct Burning;
+
+impl Drop for Burning {
+    fn drop(&mut self) {
+        BURNING_DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Component for Burning {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+/// Four entities with a position and health, the odd ones are tagged as enemies
+fn tagged_world() -> (EcsMaster, Vec<Entity>) {
+    let mut world = EcsMaster::new();
+    let entities: Vec<_> = (0..4).map(|_| world.spawn()).collect();
+
+    for (index, &entity) in entities.iter().enumerate() {
+        world.insert(entity, Position([index as f32; 2]));
+        world.insert(entity, Health(index as u32 * 10));
+        if index % 2 == 1 {
+            world.insert(entity, Enemy);
+        }
+    }
+
+    (world, entities)
+}
+
+#[test]
+fn marker_moves_the_entity_without_a_column() {
+    let (world, entities) = tagged_world();
+
+    let plain = world.location(entities[0]).unwrap().archetype_id;
+    let tagged = world.location(entities[1]).unwrap().archetype_id;
+    assert_ne!(plain, tagged);
+
+    let archetype = world.archetype(tagged).unwrap();
+    assert!(archetype.contains(Enemy::component_id()));
+    assert!(archetype.column(Enemy::component_id()).is_none());
+    assert!(archetype.marker_ptr::<Enemy>().is_some());
+    assert!(archetype.column(Position::component_id()).is_some());
+    assert!(world.archetype(plain).unwrap().marker_ptr::<Enemy>().is_none());
+
+    assert_eq!(world.get::<Enemy>(entities[1]), Some(&Enemy));
+    assert_eq!(world.get::<Position>(entities[1]), Some(&Position([1.0; 2])));
+    assert_eq!(world.get::<Health>(entities[1]), Some(&Health(10)));
+}
+
+#[test]
+fn filters_select_tagged_entities() {
+    let (mut world, entities) = tagged_world();
+
+    let mut enemies: Vec<_> = world.query_filtered::<(Entity, &Health), With<Enemy>>()
+        .map(|(entity, health)| (entity, health.0))
+        .collect();
+    enemies.sort_by_key(|&(_, health)| health);
+    assert_eq!(enemies, [(entities[1], 10), (entities[3], 30)]);
+
+    let mut others: Vec<_> = world.query_filtered::<&Health, Without<Enemy>>()
+        .map(|health| health.0)
+        .collect();
+    others.sort_unstable();
+    assert_eq!(others, [0, 20]);
+
+    // Markers can be fetched like any other component
+    assert_eq!(world.query::<(&Enemy, &Position)>().count(), 2);
+}
+
+#[test]
+fn removing_a_marker_moves_the_entity_back() {
+    let (mut world, entities) = tagged_world();
+    let plain = world.location(entities[0]).unwrap().archetype_id;
+
+    assert_eq!(world.remove::<Enemy>(entities[3]), Some(Enemy));
+    assert_eq!(world.remove::<Enemy>(entities[3]), None);
+
+    assert!(!world.has::<Enemy>(entities[3]));
+    assert_eq!(world.location(entities[3]).unwrap().archetype_id, plain);
+    assert_eq!(world.get::<Position>(entities[3]), Some(&Position([3.0; 2])));
+    assert_eq!(world.get::<Health>(entities[3]), Some(&Health(30)));
+
+    // The entity left behind in the tagged archetype kept its components too
+    assert_eq!(world.get::<Health>(entities[1]), Some(&Health(10)));
+    assert_eq!(world.query_filtered::<&Health, With<Enemy>>().count(), 1);
+}
+
+#[test]
+fn markers_with_drop_glue_are_dropped_once() {
+    let mut world = EcsMaster::new();
+    let first = world.spawn();
+    let second = world.spawn();
+
+    world.insert(first, Burning);
+    world.insert(second, Burning);
+    assert_eq!(BURNING_DROPS.load(Ordering::Relaxed), 0);
+
+    // The archetype already has the marker, the new value is dropped right away
+    world.insert(first, Burning);
+    assert_eq!(BURNING_DROPS.load(Ordering::Relaxed), 1);
+
+    drop(world.remove::<Burning>(first));
+    assert_eq!(BURNING_DROPS.load(Ordering::Relaxed), 2);
+
+    world.despawn(second);
+    assert_eq!(BURNING_DROPS.load(Ordering::Relaxed), 3);
+}
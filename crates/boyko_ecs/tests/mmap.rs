@@ -0,0 +1,128 @@
+//! Mmap arena backend: the address space is reserved up front and committed as
+//! blocks are handed out, large freed blocks give their pages back to the OS, and
+//! arenas on the system allocator keep working next to mapped ones.
+#![cfg(target_os = "linux")]
+
+use std::alloc::Layout;
+use boyko_ecs::ecs::constants::{HUGE_PAGE_SIZE, MMAP_RELEASE_THRESHOLD};
+use boyko_ecs::ecs::memory::arena::{Arena, ArenaBackend, ArenaConfig};
+use boyko_ecs::ecs::memory::mmap::HugePages;
+
+const RESERVED: usize = 64 * 1024 * 1024;
+
+/// Above the release threshold, so freeing it hands its pages back
+const LARGE: usize = 4 * MMAP_RELEASE_THRESHOLD;
+
+fn block(size: usize) -> Layout {
+    Layout::from_size_align(size, 8).unwrap()
+}
+
+fn mmap_arena(huge_pages: HugePages) -> Arena {
+    Arena::with_config(ArenaConfig {
+        capacity: 1024 * 1024,
+        max_capacity: RESERVED,
+        backend: ArenaBackend::Mmap(huge_pages),
+        ..ArenaConfig::default()
+    })
+}
+
+#[test]
+fn reserved_space_is_committed_on_demand() {
+    let arena = mmap_arena(HugePages::Off);
+
+    // The whole range is one region, nothing is backed by memory yet
+    assert_eq!(arena.region_count(), 1);
+    assert!(arena.capacity() >= RESERVED);
+    let committed = arena.committed();
+    assert!(committed < LARGE);
+
+    let ptr = arena.allocate_layout(block(LARGE));
+    assert!(arena.committed() >= committed + LARGE);
+    assert!(arena.committed() < arena.capacity());
+
+    // Committed pages are readable and writable
+    unsafe { ptr.as_ptr().write_bytes(0x5A, LARGE) };
+    let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), LARGE) };
+    assert!(bytes.iter().all(|&byte| byte == 0x5A));
+
+    // Everything up to the reservation fits without a second region
+    let rest = arena.allocate_layout(block(RESERVED / 2));
+    unsafe { rest.as_ptr().add(RESERVED / 2 - 1).write(1) };
+    assert_eq!(arena.region_count(), 1);
+
+    arena.deallocate(rest, block(RESERVED / 2));
+    arena.deallocate(ptr, block(LARGE));
+    assert_eq!(arena.stats().used_bytes, 0);
+}
+
+#[test]
+fn transparent_huge_pages_are_only_a_hint() {
+    let arena = mmap_arena(HugePages::Transparent);
+    assert_eq!(arena.capacity() % HUGE_PAGE_SIZE, 0);
+
+    let ptr = arena.allocate_layout(block(LARGE));
+    unsafe { ptr.as_ptr().write_bytes(0x11, LARGE) };
+    assert_eq!(arena.committed() % HUGE_PAGE_SIZE, 0);
+    arena.deallocate(ptr, block(LARGE));
+}
+
+// The `debug-arena` feature fills new blocks, which hides the zeroed pages
+#[cfg(not(feature = "debug-arena"))]
+#[test]
+fn large_freed_blocks_release_their_pages() {
+    let arena = mmap_arena(HugePages::Off);
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+
+    let ptr = arena.allocate_layout(block(LARGE));
+    unsafe { ptr.as_ptr().write_bytes(0xEE, LARGE) };
+    arena.deallocate(ptr, block(LARGE));
+
+    // The same block comes back, its whole pages were dropped with `MADV_DONTNEED`
+    let again = arena.allocate_layout(block(LARGE));
+    assert_eq!(again, ptr);
+    let first_page = (ptr.as_ptr() as usize).next_multiple_of(page_size) - ptr.as_ptr() as usize;
+    let bytes = unsafe { std::slice::from_raw_parts(again.as_ptr(), LARGE) };
+    assert!(bytes[first_page..first_page + LARGE / 2].iter().all(|&byte| byte == 0));
+
+    // Small blocks keep their pages, they are likely to be reused soon
+    let small = block(MMAP_RELEASE_THRESHOLD / 2);
+    let kept = arena.allocate_layout(small);
+    unsafe { kept.as_ptr().write_bytes(0xEE, small.size()) };
+    arena.deallocate(kept, small);
+    let reused = arena.allocate_layout(small);
+    assert_eq!(reused, kept);
+    assert_eq!(unsafe { reused.as_ptr().add(small.size() / 2).read() }, 0xEE);
+
+    arena.deallocate(reused, small);
+    arena.deallocate(again, block(LARGE));
+}
+
+#[test]
+fn system_and_mmap_arenas_work_side_by_side() {
+    let mapped = mmap_arena(HugePages::Off);
+    let system = Arena::with_limit(1024 * 1024, 16 * 1024 * 1024);
+
+    let mut blocks = Vec::new();
+    for index in 0..64usize {
+        let size = 1024 * (index % 16 + 1);
+        let arena = if index % 2 == 0 { &mapped } else { &system };
+        let ptr = arena.allocate_layout(block(size));
+        unsafe { ptr.as_ptr().write_bytes(index as u8, size) };
+        blocks.push((arena, ptr, size, index as u8));
+    }
+
+    for &(_, ptr, size, fill) in &blocks {
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), size) };
+        assert!(bytes.iter().all(|&byte| byte == fill), "block {fill} was overwritten");
+    }
+
+    // System regions are fully backed from the start
+    assert_eq!(system.committed(), system.capacity());
+    assert!(mapped.committed() < mapped.capacity());
+
+    for (arena, ptr, size, _) in blocks {
+        arena.deallocate(ptr, block(size));
+    }
+    assert_eq!(mapped.stats().used_bytes, 0);
+    assert_eq!(system.stats().used_bytes, 0);
+}
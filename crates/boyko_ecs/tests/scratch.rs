@@ -0,0 +1,96 @@
+//! Arena scratch memory: scopes rewind the cursor when they are dropped, a reset
+//! only moves the cursor back, and a full region fails instead of growing.
+
+use std::alloc::Layout;
+use boyko_ecs::ecs::error::EcsError;
+use boyko_ecs::ecs::memory::arena::Arena;
+
+const SCRATCH: usize = 4096;
+
+fn scratch_arena() -> Arena {
+    Arena::with_scratch(64 * 1024, 64 * 1024, SCRATCH)
+}
+
+#[test]
+fn nested_scopes_rewind_in_order() {
+    let arena = scratch_arena();
+    let outer = arena.scratch_scope().unwrap();
+    let first = outer.alloc(1u64).unwrap();
+    let after_first = arena.scratch_used();
+
+    {
+        let inner = outer.scope();
+        let values = inner.alloc_slice_copy(&[2u32; 64]).unwrap();
+        assert_eq!(values, &[2; 64][..]);
+        assert!(arena.scratch_used() >= after_first + 64 * 4);
+
+        let innermost = inner.scope();
+        innermost.alloc([3u8; 100]).unwrap();
+    }
+
+    // The inner scopes are gone, the outer value is untouched
+    assert_eq!(arena.scratch_used(), after_first);
+    assert_eq!(*first, 1);
+
+    // Allocations after the rewind reuse the inner scopes' memory
+    let second = outer.alloc(4u64).unwrap();
+    assert_eq!(arena.scratch_used(), after_first + 8);
+    assert_eq!((*first, *second), (1, 4));
+
+    drop(outer);
+    assert_eq!(arena.scratch_used(), 0);
+}
+
+#[test]
+fn interleaved_scope_stops_rewinding() {
+    let arena = scratch_arena();
+    let first = arena.scratch_scope().unwrap();
+    let second = arena.scratch_scope().unwrap();
+
+    first.alloc(1u64).unwrap();
+    second.alloc(2u64).unwrap();
+    let value = first.alloc(3u64).unwrap();
+
+    // Rewinding either scope would free memory the other one still uses
+    drop(second);
+    assert_eq!(*value, 3);
+    assert_eq!(arena.scratch_used(), 24);
+    drop(first);
+    assert_eq!(arena.scratch_used(), 24);
+}
+
+#[test]
+fn reset_moves_the_cursor_back() {
+    let mut arena = scratch_arena();
+    let first = arena.scratch_alloc(0u64).unwrap();
+    for value in 1..256u64 {
+        arena.scratch_alloc(value).unwrap();
+    }
+    assert_eq!(arena.scratch_used(), 256 * 8);
+
+    // Nothing is walked or freed, the region simply starts over
+    arena.reset_scratch();
+    assert_eq!(arena.scratch_used(), 0);
+    assert_eq!(arena.scratch_alloc(7u64).unwrap(), first);
+}
+
+#[test]
+fn full_region_runs_out_of_memory() {
+    let mut arena = scratch_arena();
+    let scope = arena.scratch_scope().unwrap();
+    scope.alloc([0u8; SCRATCH - 16]).unwrap();
+
+    let error = scope.alloc([0u8; 32]).unwrap_err();
+    assert_eq!(error, EcsError::OutOfMemory { size: 32, align: 1 });
+    assert!(arena.scratch_used() <= SCRATCH);
+
+    // What still fits is handed out, zero-sized values always are
+    scope.alloc([0u8; 16]).unwrap();
+    scope.alloc(()).unwrap();
+    assert_eq!(arena.scratch_used(), SCRATCH);
+    assert!(arena.scratch_alloc_layout(Layout::new::<u64>()).is_err());
+
+    drop(scope);
+    arena.reset_scratch();
+    assert!(arena.scratch_alloc([1u8; SCRATCH]).is_ok());
+}
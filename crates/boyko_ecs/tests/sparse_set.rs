@@ -0,0 +1,280 @@
+//! Sparse-set storage: dense packing with swap-back removal, and queries that mix
+//! table and sparse components without the caller noticing the difference.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Arc;
+use boyko_ecs::ecs::core::component::{dynamic_component_id, Component, ComponentId, StorageType};
+use boyko_ecs::ecs::core::ecs_master::EcsMaster;
+use boyko_ecs::ecs::core::entity::Entity;
+use boyko_ecs::ecs::core::query::{With, Without};
+use boyko_ecs::ecs::error::EcsError;
+use boyko_ecs::ecs::memory::chunk_allocator::{ChunkAllocator, SystemChunkAllocator};
+use boyko_ecs::ecs::memory::sparse_set::SparseSet;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Position(f32);
+
+impl Component for Position {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Selected(u32);
+
+impl Component for Selected {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+
+    fn storage_type() -> StorageType {
+        StorageType::Sparse
+    }
+}
+
+/// Counts its drops in a shared counter
+struct Tracked {
+    drops: Rc<Cell<usize>>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+    }
+}
+
+impl Component for Tracked {
+    fn component_id() -> ComponentId {
+        dynamic_component_id::<Self>()
+    }
+
+    fn storage_type() -> StorageType {
+        StorageType::Sparse
+    }
+}
+
+fn system() -> Arc<dyn ChunkAllocator> {
+    Arc::new(SystemChunkAllocator)
+}
+
+/// Sparse set with two components per chunk, so a handful of entities spans several chunks
+fn small_set<T: Component>() -> SparseSet<T> {
+    SparseSet::new(&system(), 2)
+}
+
+#[test]
+fn insert_and_get() {
+    let mut set = small_set::<Selected>();
+    assert!(set.is_empty());
+
+    for id in [7, 3, 12] {
+        assert_eq!(set.insert(Entity::with_id(id), Selected(id * 10)), None);
+    }
+
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.get(3), Some(&Selected(30)));
+    assert_eq!(set.get(12), Some(&Selected(120)));
+    assert!(set.contains(7));
+    assert!(!set.contains(4));
+    assert_eq!(set.get(100), None);
+
+    let ids: Vec<_> = set.entities().iter().map(Entity::id).collect();
+    assert_eq!(ids, [7, 3, 12]);
+}
+
+#[test]
+fn insert_replaces_the_existing_component() {
+    let mut set = small_set::<Selected>();
+    set.insert(Entity::with_id(1), Selected(1));
+
+    assert_eq!(set.insert(Entity::with_id(1), Selected(2)), Some(Selected(1)));
+    assert_eq!(set.len(), 1);
+    assert_eq!(set.get(1), Some(&Selected(2)));
+}
+
+#[test]
+fn remove_swaps_the_last_component_back() {
+    let mut set = small_set::<Selected>();
+    for id in 0..5 {
+        set.insert(Entity::with_id(id), Selected(id));
+    }
+
+    // The last component fills the hole and its sparse entry follows it
+    assert_eq!(set.remove(1), Some(Selected(1)));
+    let ids: Vec<_> = set.entities().iter().map(Entity::id).collect();
+    assert_eq!(ids, [0, 4, 2, 3]);
+
+    assert!(!set.contains(1));
+    assert_eq!(set.get(1), None);
+    for id in [0, 2, 3, 4] {
+        assert_eq!(set.get(id), Some(&Selected(id)), "entity {id}");
+    }
+
+    // Removing the last dense component moves nothing
+    assert_eq!(set.remove(3), Some(Selected(3)));
+    assert_eq!(set.remove(3), None);
+    for id in [0, 2, 4] {
+        assert_eq!(set.get(id), Some(&Selected(id)), "entity {id}");
+    }
+}
+
+#[test]
+fn reinsert_after_remove() {
+    let mut set = small_set::<Selected>();
+    for id in 0..4 {
+        set.insert(Entity::with_id(id), Selected(id));
+    }
+
+    set.remove(0);
+    set.remove(2);
+    assert_eq!(set.insert(Entity::with_id(0), Selected(100)), None);
+    assert_eq!(set.insert(Entity::with_id(2), Selected(200)), None);
+
+    assert_eq!(set.len(), 4);
+    assert_eq!(set.get(0), Some(&Selected(100)));
+    assert_eq!(set.get(1), Some(&Selected(1)));
+    assert_eq!(set.get(2), Some(&Selected(200)));
+    assert_eq!(set.get(3), Some(&Selected(3)));
+}
+
+#[test]
+fn every_component_is_dropped_once() {
+    let drops = Rc::new(Cell::new(0));
+    let mut set = small_set::<Tracked>();
+    for id in 0..5 {
+        set.insert(Entity::with_id(id), Tracked { drops: drops.clone() });
+    }
+
+    // The replaced component goes back to the caller
+    let old = set.insert(Entity::with_id(0), Tracked { drops: drops.clone() });
+    assert_eq!(drops.get(), 0);
+    drop(old);
+    assert_eq!(drops.get(), 1);
+
+    drop(set.remove(2));
+    assert_eq!(drops.get(), 2);
+
+    drop(set);
+    assert_eq!(drops.get(), 6);
+}
+
+#[test]
+fn chunks_must_hold_a_component() {
+    assert!(matches!(SparseSet::<Selected>::try_new(&system(), 0), Err(EcsError::InvalidLayout)));
+}
+
+#[test]
+fn trailing_empty_chunks_are_released() {
+    let mut set = small_set::<Selected>();
+    for id in 0..10 {
+        set.insert(Entity::with_id(id), Selected(id));
+    }
+    assert_eq!(set.chunks_count(), 5);
+
+    // One empty chunk is kept for the next inserts
+    set.remove(9);
+    set.remove(8);
+    assert_eq!(set.chunks_count(), 5);
+
+    // The second one goes back to the allocator
+    set.remove(7);
+    set.remove(6);
+    assert_eq!(set.chunks_count(), 4);
+
+    for id in (0..6).rev() {
+        set.remove(id);
+    }
+    assert!(set.is_empty());
+    assert_eq!(set.chunks_count(), 1);
+
+    // The kept chunk is reused and new chunks are taken again once it is full
+    for id in 0..3 {
+        set.insert(Entity::with_id(id), Selected(id));
+    }
+    assert_eq!(set.chunks_count(), 2);
+    for id in 0..3 {
+        assert_eq!(set.get(id), Some(&Selected(id)));
+    }
+}
+
+/// World where every odd entity also has a sparse `Selected`
+fn interleaved_world() -> (EcsMaster, Vec<Entity>) {
+    let mut world = EcsMaster::new();
+    let entities: Vec<_> = (0..10).map(|_| world.spawn()).collect();
+
+    for (index, &entity) in entities.iter().enumerate() {
+        world.insert(entity, Position(index as f32));
+        if index % 2 == 1 {
+            world.insert(entity, Selected(index as u32));
+        }
+    }
+
+    (world, entities)
+}
+
+#[test]
+fn query_mixes_table_and_sparse_components() {
+    let (mut world, entities) = interleaved_world();
+
+    let mut matched: Vec<_> = world.query::<(Entity, &Position, &Selected)>()
+        .map(|(entity, position, selected)| (entity.id(), position.0, selected.0))
+        .collect();
+    matched.sort_by_key(|&(id, _, _)| id);
+
+    let expected: Vec<_> = entities.iter().enumerate()
+        .filter(|(index, _)| index % 2 == 1)
+        .map(|(index, entity)| (entity.id(), index as f32, index as u32))
+        .collect();
+    assert_eq!(matched, expected);
+
+    // Sparse components stay out of the archetypes
+    assert_eq!(world.archetypes().iter().filter(|archetype| !archetype.is_empty()).count(), 1);
+}
+
+#[test]
+fn query_writes_sparse_components() {
+    let (mut world, entities) = interleaved_world();
+
+    for (position, selected) in world.query::<(&Position, &mut Selected)>() {
+        selected.0 += position.0 as u32;
+    }
+
+    for (index, &entity) in entities.iter().enumerate() {
+        let expected = (index % 2 == 1).then_some(Selected(2 * index as u32));
+        assert_eq!(world.get::<Selected>(entity).copied(), expected, "entity {index}");
+    }
+}
+
+#[test]
+fn filters_on_sparse_components() {
+    let (mut world, _) = interleaved_world();
+
+    let mut with: Vec<_> = world.query_filtered::<&Position, With<Selected>>()
+        .map(|position| position.0 as usize)
+        .collect();
+    with.sort_unstable();
+    assert_eq!(with, [1, 3, 5, 7, 9]);
+
+    let mut without: Vec<_> = world.query_filtered::<&Position, Without<Selected>>()
+        .map(|position| position.0 as usize)
+        .collect();
+    without.sort_unstable();
+    assert_eq!(without, [0, 2, 4, 6, 8]);
+}
+
+#[test]
+fn sparse_removal_keeps_queries_consistent() {
+    let (mut world, entities) = interleaved_world();
+
+    assert_eq!(world.remove::<Selected>(entities[3]), Some(Selected(3)));
+    world.despawn(entities[5]);
+    world.insert(entities[4], Selected(4));
+
+    let mut matched: Vec<_> = world.query::<(&Position, &Selected)>()
+        .map(|(position, selected)| (position.0 as u32, selected.0))
+        .collect();
+    matched.sort_unstable();
+    assert_eq!(matched, [(1, 1), (4, 4), (7, 7), (9, 9)]);
+}
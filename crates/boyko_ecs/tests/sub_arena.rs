@@ -0,0 +1,82 @@
+//! Sub-arenas: dropping one gives its whole block back to the parent, and the
+//! allocations still inside it are discarded instead of reported as leaks.
+
+use std::alloc::Layout;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use boyko_ecs::ecs::error::EcsError;
+use boyko_ecs::ecs::memory::allocation_tracker::LeakReport;
+use boyko_ecs::ecs::memory::arena::{Arena, ArenaConfig};
+
+const SUB_ARENA: usize = 64 * 1024;
+
+fn block(size: usize) -> Layout {
+    Layout::from_size_align(size, 8).unwrap()
+}
+
+static LEAK_REPORTS: AtomicUsize = AtomicUsize::new(0);
+
+fn count_leaks(_report: &LeakReport) {
+    LEAK_REPORTS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn dropped_sub_arena_returns_its_block() {
+    let parent = Arena::with_limit(1024 * 1024, 1024 * 1024);
+
+    let sub = parent.sub_arena(SUB_ARENA);
+    assert_eq!(sub.capacity(), SUB_ARENA);
+    assert_eq!(parent.stats().used_bytes, SUB_ARENA);
+    assert_eq!(parent.stats().owners[0].owner, "SubArena");
+
+    // Allocations inside come out of the block, not out of the parent
+    let first = sub.allocate_layout(block(8 * 1024));
+    for _ in 0..100 {
+        sub.allocate_layout(block(100));
+    }
+    assert_eq!(parent.stats().used_bytes, SUB_ARENA);
+
+    drop(sub);
+    let stats = parent.stats();
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.allocation_count, 0);
+    assert!(stats.owners.is_empty());
+
+    // The next sub-arena gets the same block back, empty
+    let again = parent.sub_arena(SUB_ARENA);
+    assert_eq!(again.stats().used_bytes, 0);
+    assert_eq!(again.allocate_layout(block(8 * 1024)), first);
+}
+
+#[test]
+fn sub_arena_never_outgrows_its_block() {
+    let parent = Arena::new();
+    let sub = parent.sub_arena(SUB_ARENA);
+
+    let error = sub.try_allocate_layout(block(2 * SUB_ARENA)).unwrap_err();
+    assert!(matches!(error, EcsError::OutOfMemory { .. }), "{error}");
+    assert_eq!(sub.region_count(), 1);
+    assert_eq!(parent.try_sub_arena(0).err(), Some(EcsError::InvalidLayout));
+}
+
+#[test]
+fn discarded_allocations_are_not_leaks() {
+    let parent = Arena::with_config(ArenaConfig { leak_hook: Some(count_leaks), ..ArenaConfig::default() });
+
+    {
+        let sub = parent.sub_arena(SUB_ARENA);
+        let nested = sub.sub_arena(SUB_ARENA / 2);
+        nested.allocate_layout(block(64));
+        sub.allocate_layout(block(64));
+
+        // Allocations are tracked while the sub-arena lives
+        if cfg!(any(debug_assertions, feature = "debug-arena")) {
+            assert_eq!(sub.leak_report().allocations.len(), 2);
+            assert_eq!(parent.leak_report().allocations.len(), 1);
+        }
+    }
+
+    // The block went back through the parent's normal free path
+    assert!(parent.leak_report().is_empty());
+    drop(parent);
+    assert_eq!(LEAK_REPORTS.load(Ordering::Relaxed), 0);
+}
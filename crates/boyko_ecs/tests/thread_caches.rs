@@ -0,0 +1,61 @@
+//! Arena thread caches: blocks allocated on one thread and freed on another all
+//! find their way back, and flushing the caches returns every cached byte.
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::sync::mpsc;
+use std::thread;
+use boyko_ecs::ecs::memory::arena::Arena;
+
+const THREADS: usize = 4;
+
+const BLOCKS_PER_THREAD: usize = 2000;
+
+/// Sizes of every cached class and a few that bypass the caches
+fn layout(index: usize) -> Layout {
+    let size = [8, 16, 24, 64, 100, 256, 1000, 4096, 5000][index % 9];
+    Layout::from_size_align(size, 8).unwrap()
+}
+
+#[test]
+fn blocks_freed_on_other_threads_are_returned() {
+    let arena = Arena::new();
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..THREADS).map(|_| mpsc::channel::<(usize, Layout, u8)>()).unzip();
+
+    thread::scope(|scope| {
+        for (thread_index, receiver) in receivers.into_iter().enumerate() {
+            // Each thread hands its blocks to the next one and frees what the previous one sent
+            let sender = senders[(thread_index + 1) % THREADS].clone();
+            let arena = &arena;
+
+            scope.spawn(move || {
+                for index in 0..BLOCKS_PER_THREAD {
+                    let layout = layout(index);
+                    let ptr = arena.allocate_layout(layout);
+                    let fill = (thread_index * 31 + index) as u8;
+                    unsafe { ptr.as_ptr().write_bytes(fill, layout.size()) };
+                    sender.send((ptr.as_ptr() as usize, layout, fill)).unwrap();
+                }
+                drop(sender);
+
+                for (address, layout, fill) in receiver {
+                    let ptr = NonNull::new(address as *mut u8).unwrap();
+                    let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), layout.size()) };
+                    assert!(bytes.iter().all(|&byte| byte == fill), "block was handed out twice");
+                    arena.deallocate(ptr, layout);
+                }
+            });
+        }
+        drop(senders);
+    });
+
+    let stats = arena.stats();
+    assert_eq!(stats.used_bytes, 0);
+    assert_eq!(stats.allocation_count, 0);
+    assert_eq!(stats.total_allocations, THREADS * BLOCKS_PER_THREAD);
+
+    arena.flush_thread_caches();
+    let stats = arena.stats();
+    assert_eq!(stats.cached_bytes, 0);
+    assert_eq!(stats.free_bytes, stats.capacity);
+}
@@ -0,0 +1,139 @@
+//! TLSF free blocks seen through an arena region: size classes, splitting and merging,
+//! and aligned allocation that gives the skipped space back.
+//!
+//! The canaries of the `debug-arena` feature change every block size, so exact
+//! placement is only checked without it.
+#![cfg(not(feature = "debug-arena"))]
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use boyko_ecs::ecs::memory::arena::{Arena, ArenaConfig, FreeBlockStrategy};
+
+const CAPACITY: usize = 64 * 1024;
+
+/// Single-region TLSF arena that never grows
+fn tlsf_arena() -> Arena {
+    Arena::with_config(ArenaConfig {
+        capacity: CAPACITY,
+        max_capacity: CAPACITY,
+        free_blocks: FreeBlockStrategy::Tlsf,
+        ..ArenaConfig::default()
+    })
+}
+
+fn layout(size: usize, align: usize) -> Layout {
+    Layout::from_size_align(size, align).unwrap()
+}
+
+/// Takes a block from the free lists, bypassing the thread caches
+fn take(arena: &Arena, size: usize, align: usize) -> NonNull<u8> {
+    arena.allocate_from_free_blocks(layout(size, align))
+        .unwrap_or_else(|| panic!("no block for {size} bytes aligned to {align}"))
+}
+
+fn give_back(arena: &Arena, ptr: NonNull<u8>, size: usize, align: usize) {
+    arena.deallocate_to_free_blocks(ptr, layout(size, align));
+}
+
+#[test]
+fn requests_land_in_the_hole_of_their_class() {
+    let arena = tlsf_arena();
+
+    // Holes at the lower bound of their class, from the small exact classes up to
+    // the coarse ones, separated by live blocks so they cannot merge
+    let sizes = [8, 40, 128, 192, 1024, 3072];
+    let holes: Vec<_> = sizes.iter()
+        .map(|&size| {
+            let hole = take(&arena, size, 8);
+            take(&arena, 64, 8);
+            hole
+        })
+        .collect();
+    for (&hole, &size) in holes.iter().zip(&sizes) {
+        give_back(&arena, hole, size, 8);
+    }
+
+    for (&hole, &size) in holes.iter().zip(&sizes).rev() {
+        assert_eq!(take(&arena, size, 8), hole, "{size} bytes");
+    }
+}
+
+#[test]
+fn larger_requests_skip_smaller_holes() {
+    let arena = tlsf_arena();
+    let hole = take(&arena, 1024, 8);
+    take(&arena, 64, 8);
+    give_back(&arena, hole, 1024, 8);
+
+    // One granule more than the hole holds never lands in it
+    let larger = take(&arena, 1032, 8);
+    assert_ne!(larger, hole);
+    assert_eq!(take(&arena, 1024, 8), hole);
+}
+
+#[test]
+fn split_blocks_merge_back() {
+    let arena = tlsf_arena();
+    let blocks: Vec<_> = (0..4).map(|_| take(&arena, 4096, 8)).collect();
+
+    let stats = arena.stats();
+    assert_eq!(stats.free_bytes, CAPACITY - 4 * 4096);
+    assert_eq!(stats.free_block_count, 1);
+
+    // Neither neighbour is free, then the left one, then both
+    give_back(&arena, blocks[1], 4096, 8);
+    assert_eq!(arena.stats().free_block_count, 2);
+    give_back(&arena, blocks[3], 4096, 8);
+    assert_eq!(arena.stats().free_block_count, 2);
+    give_back(&arena, blocks[2], 4096, 8);
+    assert_eq!(arena.stats().free_block_count, 1);
+    give_back(&arena, blocks[0], 4096, 8);
+
+    let stats = arena.stats();
+    assert_eq!(stats.free_block_count, 1);
+    assert_eq!(stats.free_bytes, CAPACITY);
+    assert_eq!(take(&arena, CAPACITY, 8), blocks[0]);
+}
+
+#[test]
+fn aligned_allocation_returns_the_skipped_space() {
+    let arena = tlsf_arena();
+    let first = take(&arena, 8, 8);
+
+    let aligned = take(&arena, 64, 4096);
+    assert_eq!(aligned.as_ptr() as usize % 4096, 0);
+
+    // The space between the two blocks is free again, next to the tail
+    let stats = arena.stats();
+    assert_eq!(stats.free_block_count, 2);
+    assert_eq!(stats.free_bytes, CAPACITY - 8 - 64);
+
+    // Freeing both blocks merges all three free ranges into the whole region again
+    give_back(&arena, aligned, 64, 4096);
+    give_back(&arena, first, 8, 8);
+    let stats = arena.stats();
+    assert_eq!(stats.free_block_count, 1);
+    assert_eq!(stats.free_bytes, CAPACITY);
+}
+
+#[test]
+fn aligned_exact_fit_takes_the_whole_region() {
+    let arena = tlsf_arena();
+    let block = take(&arena, CAPACITY, 64);
+    assert_eq!(arena.stats().free_bytes, 0);
+
+    give_back(&arena, block, CAPACITY, 64);
+    assert_eq!(arena.stats().free_bytes, CAPACITY);
+}
+
+#[test]
+fn largest_free_block_can_always_be_taken() {
+    let arena = tlsf_arena();
+    assert_eq!(arena.stats().largest_free_block, CAPACITY);
+
+    // 65528 free bytes fall into the class starting at 63488, one 2048-byte step lower
+    take(&arena, 8, 8);
+    let largest = arena.stats().largest_free_block;
+    assert_eq!(largest, 63488);
+    take(&arena, largest, 8);
+}
@@ -1,32 +1,149 @@
 
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use syn::{parse_macro_input, parse_quote, DeriveInput, LitInt, LitStr};
 
-// Global counter for component IDs
-static COMPONENT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Settings collected from `#[component(...)]` attributes
+#[derive(Default)]
+struct ComponentAttributes {
+    storage: Option<TokenStream2>,
+    chunk_capacity: Option<usize>,
+    name: Option<LitStr>,
+}
+
+impl ComponentAttributes {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attributes = Self::default();
+
+        for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("component")) {
+            attr.parse_nested_meta(|meta| {
+                let duplicate = |key: &str| meta.error(format!("duplicate component attribute `{key}`"));
+
+                if meta.path.is_ident("storage") {
+                    if attributes.storage.is_some() {
+                        return Err(duplicate("storage"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    let storage = match value.value().as_str() {
+                        "table" => quote!(Table),
+                        "sparse" => quote!(Sparse),
+                        _ => return Err(syn::Error::new(
+                            value.span(),
+                            "expected `\"table\"` or `\"sparse\"`",
+                        )),
+                    };
+                    attributes.storage = Some(storage);
+                    Ok(())
+                } else if meta.path.is_ident("chunk_capacity") {
+                    if attributes.chunk_capacity.is_some() {
+                        return Err(duplicate("chunk_capacity"));
+                    }
+                    let value: LitInt = meta.value()?.parse()?;
+                    let capacity = value.base10_parse::<usize>()?;
+                    if capacity == 0 {
+                        return Err(syn::Error::new(value.span(), "chunk_capacity must be positive"));
+                    }
+                    attributes.chunk_capacity = Some(capacity);
+                    Ok(())
+                } else if meta.path.is_ident("name") {
+                    if attributes.name.is_some() {
+                        return Err(duplicate("name"));
+                    }
+                    attributes.name = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown component attribute, expected `storage`, `chunk_capacity` or `name`"))
+                }
+            })?;
+        }
+
+        Ok(attributes)
+    }
+}
 
 /// Derive macro for implementing the Component trait
 ///
 /// This macro automatically generates all required methods for the Component trait.
 /// It assigns a unique ID to each component type and provides efficient methods
 /// for accessing type information.
-#[proc_macro_derive(Component)]
+///
+/// IDs are handed out densely at runtime on first use, so they fit the inline bits of
+/// component masks. Non-generic types cache their ID in a `ComponentIdCell`, every
+/// instantiation of a generic type gets its own ID through `dynamic_component_id`.
+///
+/// Supported attributes:
+/// * `#[component(storage = "table" | "sparse")]` - storage backend for the component
+/// * `#[component(chunk_capacity = 512)]` - number of components per chunk
+/// * `#[component(name = "...")]` - name reported instead of the Rust type name
+#[proc_macro_derive(Component, attributes(component))]
 pub fn component_macro(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let name = input.ident;
 
-    let component_id = COMPONENT_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let attributes = match ComponentAttributes::parse(&input) {
+        Ok(attributes) => attributes,
+        Err(error) => return error.to_compile_error().into(),
+    };
 
-    let expanded = quote! {
-        impl boyko_ecs::ecs::core::component::Component for #name {
-            #[inline(always)]
+    let name = &input.ident;
+
+    // Component requires 'static, so every generic parameter has to be 'static as well
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!('static));
+    }
+    for param in generics.lifetimes_mut() {
+        param.bounds.push(parse_quote!('static));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let component_id = if input.generics.params.is_empty() {
+        quote! {
+            #[inline]
+            fn component_id() -> usize {
+                static ID: boyko_ecs::ecs::core::component::ComponentIdCell =
+                    boyko_ecs::ecs::core::component::ComponentIdCell::new();
+                ID.get_or_register::<Self>()
+            }
+        }
+    } else {
+        quote! {
+            #[inline]
             fn component_id() -> usize {
-                #component_id
+                boyko_ecs::ecs::core::component::dynamic_component_id::<Self>()
             }
         }
     };
 
+    let storage = attributes.storage.map(|storage| quote! {
+        #[inline(always)]
+        fn storage_type() -> boyko_ecs::ecs::core::component::StorageType {
+            boyko_ecs::ecs::core::component::StorageType::#storage
+        }
+    });
+
+    let chunk_capacity = attributes.chunk_capacity.map(|capacity| quote! {
+        #[inline(always)]
+        fn chunk_capacity() -> Option<usize> {
+            Some(#capacity)
+        }
+    });
+
+    let component_name = attributes.name.map(|component_name| quote! {
+        #[inline(always)]
+        fn name() -> &'static str {
+            #component_name
+        }
+    });
+
+    let expanded = quote! {
+        impl #impl_generics boyko_ecs::ecs::core::component::Component for #name #ty_generics #where_clause {
+            #component_id
+            #storage
+            #chunk_capacity
+            #component_name
+        }
+    };
+
     expanded.into()
-}
\ No newline at end of file
+}
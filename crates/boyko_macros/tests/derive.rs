@@ -0,0 +1,128 @@
+//! What `#[derive(Component)]` generates: IDs, attribute overrides and generic support.
+//! Attributes the derive must reject are compile-fail cases in `ui/`.
+
+use std::marker::PhantomData;
+use boyko_ecs::ecs::constants::COMPONENT_MASK_INLINE_BITS;
+use boyko_ecs::ecs::core::component::{Component, StorageType};
+use boyko_macros::Component;
+
+#[derive(Component)]
+struct Plain(#[allow(dead_code)] u32);
+
+#[derive(Component)]
+#[component(storage = "sparse")]
+struct Sparse;
+
+#[derive(Component)]
+#[component(storage = "table")]
+struct Table;
+
+#[derive(Component)]
+#[component(chunk_capacity = 512)]
+struct Chunked;
+
+#[derive(Component)]
+#[component(name = "Health")]
+struct Named;
+
+#[derive(Component)]
+#[component(storage = "sparse", chunk_capacity = 64)]
+#[component(name = "Split")]
+struct Combined;
+
+#[derive(Component)]
+#[allow(dead_code)]
+enum State {
+    Idle,
+    Running(u32),
+}
+
+#[derive(Component)]
+struct Wrapper<T>(#[allow(dead_code)] T);
+
+#[derive(Component)]
+#[component(storage = "sparse")]
+struct Bounded<T>(PhantomData<T>)
+where
+    T: Copy;
+
+#[test]
+fn defaults_without_attributes() {
+    assert_eq!(Plain::storage_type(), StorageType::Table);
+    assert_eq!(Plain::chunk_capacity(), None);
+    assert_eq!(Plain::name(), std::any::type_name::<Plain>());
+}
+
+#[test]
+fn storage_attribute() {
+    assert_eq!(Sparse::storage_type(), StorageType::Sparse);
+    assert_eq!(Table::storage_type(), StorageType::Table);
+}
+
+#[test]
+fn chunk_capacity_attribute() {
+    assert_eq!(Chunked::chunk_capacity(), Some(512));
+    assert_eq!(Chunked::storage_type(), StorageType::Table);
+}
+
+#[test]
+fn name_attribute() {
+    assert_eq!(Named::name(), "Health");
+    assert_eq!(Named::debug_type_name(), std::any::type_name::<Named>());
+}
+
+#[test]
+fn attributes_combine_across_lists() {
+    assert_eq!(Combined::storage_type(), StorageType::Sparse);
+    assert_eq!(Combined::chunk_capacity(), Some(64));
+    assert_eq!(Combined::name(), "Split");
+}
+
+#[test]
+fn enums_are_components() {
+    assert_eq!(State::storage_type(), StorageType::Table);
+    assert_ne!(State::component_id(), Plain::component_id());
+}
+
+#[test]
+fn component_ids_are_unique_and_stable() {
+    let ids = [
+        Plain::component_id(),
+        Sparse::component_id(),
+        Table::component_id(),
+        Chunked::component_id(),
+        Named::component_id(),
+        Combined::component_id(),
+        State::component_id(),
+        Wrapper::<u32>::component_id(),
+        Wrapper::<f32>::component_id(),
+        Bounded::<u8>::component_id(),
+    ];
+
+    for (index, id) in ids.iter().enumerate() {
+        assert!(!ids[index + 1..].contains(id), "component ID {id} is used twice");
+        assert!(*id < COMPONENT_MASK_INLINE_BITS, "component ID {id} does not fit the inline mask bits");
+    }
+    assert_eq!(Plain::component_id(), ids[0]);
+    assert_eq!(Wrapper::<u32>::component_id(), ids[7]);
+}
+
+#[test]
+fn generic_instantiations_get_their_own_ids() {
+    assert_ne!(Wrapper::<u32>::component_id(), Wrapper::<u64>::component_id());
+    assert_eq!(Wrapper::<u32>::component_id(), Wrapper::<u32>::component_id());
+    assert_eq!(Wrapper::<u32>::storage_type(), StorageType::Table);
+}
+
+#[test]
+fn generic_ids_agree_across_threads() {
+    let id = Wrapper::<i16>::component_id();
+    let other = std::thread::spawn(Wrapper::<i16>::component_id).join().unwrap();
+    assert_eq!(id, other);
+}
+
+#[test]
+fn where_clauses_are_kept() {
+    assert_eq!(Bounded::<u8>::storage_type(), StorageType::Sparse);
+    assert_ne!(Bounded::<u8>::component_id(), Bounded::<u16>::component_id());
+}
@@ -0,0 +1,8 @@
+//! Attributes the derive must reject, with the expected errors in `ui/*.stderr`.
+//! Regenerate the expectations with `TRYBUILD=overwrite cargo test -p boyko-macros --test ui`.
+
+#[test]
+fn invalid_attributes() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
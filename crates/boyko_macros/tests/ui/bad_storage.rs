@@ -0,0 +1,7 @@
+use boyko_macros::Component;
+
+#[derive(Component)]
+#[component(storage = "dense")]
+struct Position;
+
+fn main() {}
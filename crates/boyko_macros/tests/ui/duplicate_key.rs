@@ -0,0 +1,7 @@
+use boyko_macros::Component;
+
+#[derive(Component)]
+#[component(storage = "table", storage = "sparse")]
+struct Position;
+
+fn main() {}
@@ -0,0 +1,8 @@
+use boyko_macros::Component;
+
+#[derive(Component)]
+#[component(name = "A")]
+#[component(name = "B")]
+struct Position;
+
+fn main() {}
@@ -0,0 +1,7 @@
+use boyko_macros::Component;
+
+#[derive(Component)]
+#[component(speed = 3)]
+struct Position;
+
+fn main() {}
@@ -0,0 +1,7 @@
+use boyko_macros::Component;
+
+#[derive(Component)]
+#[component(chunk_capacity = 0)]
+struct Position;
+
+fn main() {}
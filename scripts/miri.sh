@@ -0,0 +1,11 @@
+#!/bin/sh
+# Runs the chunk tests under Miri, which checks every slot access and drop for undefined behaviour
+#
+# Usage: scripts/miri.sh [extra cargo test arguments]
+set -eu
+
+cd "$(dirname "$0")/.."
+
+rustup toolchain install nightly --profile minimal --component miri,rust-src
+cargo +nightly miri setup
+cargo +nightly miri test -p boyko-ecs --test chunk "$@"